@@ -0,0 +1,194 @@
+//! Summaries kept across reports, so only new material reaches the model.
+//!
+//! Commit summaries are keyed by SHA, which never changes, and issue
+//! summaries by number and `updated_at`, so an issue is summarized again once
+//! it has new activity. Keys also name the user and the stage's model
+//! settings, since both shape the summary.
+//!
+//! GitHub's responses are kept apart, in the `http` subdirectory, with a cap
+//! on their total size: unlike the summaries they are large, and any of them
+//! can be fetched again.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+pub trait Cache {
+    fn get(&self, key: &str) -> Option<String>;
+    fn put(&self, key: &str, value: &str);
+}
+
+/// Builds the cache configured in the environment: a [`FileCache`] in
+/// `cache_dir` if it is set, no caching otherwise.
+pub fn from_env() -> Box<dyn Cache> {
+    match env::var("cache_dir") {
+        Ok(dir) if !dir.trim().is_empty() => Box::new(FileCache::new(dir.trim())),
+        _ => Box::new(NoCache),
+    }
+}
+
+/// Builds the cache for GitHub's responses: a [`FileCache`] in the `http`
+/// subdirectory of `cache_dir`, capped at `http_cache_max_mb` (default 100)
+/// megabytes, or no caching without a `cache_dir`.
+pub fn http_from_env() -> Box<dyn Cache> {
+    let max_mb = env::var("http_cache_max_mb")
+        .ok()
+        .and_then(|n| n.trim().parse::<u64>().ok())
+        .unwrap_or(100);
+    match env::var("cache_dir") {
+        Ok(dir) if !dir.trim().is_empty() => {
+            let dir = Path::new(dir.trim()).join("http");
+            Box::new(FileCache::new(&dir.to_string_lossy()).with_max_bytes(max_mb * 1024 * 1024))
+        }
+        _ => Box::new(NoCache),
+    }
+}
+
+/// Caches nothing.
+#[derive(Debug, Default)]
+pub struct NoCache;
+
+impl Cache for NoCache {
+    fn get(&self, _key: &str) -> Option<String> {
+        None
+    }
+
+    fn put(&self, _key: &str, _value: &str) {}
+}
+
+/// One file per entry in a directory, which is created on the first write.
+/// Failing to read or write an entry is logged and otherwise ignored.
+#[derive(Debug, Clone)]
+pub struct FileCache {
+    dir: PathBuf,
+    max_bytes: Option<u64>,
+}
+
+impl FileCache {
+    pub fn new(dir: &str) -> FileCache {
+        FileCache {
+            dir: dir.into(),
+            max_bytes: None,
+        }
+    }
+
+    /// Caps the entries at `max_bytes` in total. Each write past the cap
+    /// removes the least recently written entries until the rest fit.
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> FileCache {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let mut name: String = key
+            .chars()
+            .map(
+                |c| match c.is_ascii_alphanumeric() || matches!(c, '.' | '-') {
+                    true => c,
+                    false => '_',
+                },
+            )
+            .collect();
+        // keep names within filesystem limits, and distinct after the cut
+        if name.len() > 200 {
+            name.truncate(180);
+            name.push_str(&format!("-{:016x}", fnv1a(key)));
+        }
+        self.dir.join(format!("{name}.txt"))
+    }
+
+    /// Removes the oldest entries, other than `kept`, until the directory is
+    /// within `max_bytes`.
+    fn evict(&self, max_bytes: u64, kept: &Path) {
+        let Ok(dir) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut entries: Vec<(SystemTime, u64, PathBuf)> = dir
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "txt"))
+            .filter_map(|entry| {
+                let meta = entry.metadata().ok()?;
+                let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                meta.is_file().then(|| (modified, meta.len(), entry.path()))
+            })
+            .collect();
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort();
+        for (_, len, path) in entries {
+            if total <= max_bytes {
+                break;
+            }
+            if path == kept {
+                continue;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => total -= len,
+                Err(_e) => log::error!("Error evicting cache entry {}: {:?}", path.display(), _e),
+            }
+        }
+    }
+}
+
+impl Cache for FileCache {
+    fn get(&self, key: &str) -> Option<String> {
+        fs::read_to_string(self.path(key)).ok()
+    }
+
+    fn put(&self, key: &str, value: &str) {
+        let path = self.path(key);
+        // write aside and rename, so a reader never sees half an entry
+        let tmp = path.with_extension("tmp");
+        let written = fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(&tmp, value))
+            .and_then(|_| fs::rename(&tmp, &path));
+        if let Err(_e) = written {
+            log::error!("Error writing cache entry {}: {:?}", path.display(), _e);
+            return;
+        }
+        if let Some(max_bytes) = self.max_bytes {
+            self.evict(max_bytes, &path);
+        }
+    }
+}
+
+pub(crate) fn fnv1a(key: &str) -> u64 {
+    key.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Keeps entries in memory. Clones share the entries.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryCache {
+    entries: Rc<RefCell<HashMap<String, String>>>,
+}
+
+impl MemoryCache {
+    pub fn new() -> MemoryCache {
+        MemoryCache::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.borrow().is_empty()
+    }
+}
+
+impl Cache for MemoryCache {
+    fn get(&self, key: &str) -> Option<String> {
+        self.entries.borrow().get(key).cloned()
+    }
+
+    fn put(&self, key: &str, value: &str) {
+        self.entries
+            .borrow_mut()
+            .insert(key.to_string(), value.to_string());
+    }
+}
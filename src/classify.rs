@@ -0,0 +1,329 @@
+//! Tagging of changed files by what they are, so the summaries can tell a
+//! code change from a docs or lockfile change without asking the model.
+//!
+//! Files are classified by path: well-known file names, directories and
+//! extensions, in the order generated, test, docs, script, config, code. A
+//! file whose diff carries a generated-code marker is generated whatever its
+//! path.
+//!
+//! `file_kinds` overrides the rules, as a comma separated list of
+//! `pattern=kind`, where a pattern is a suffix of the path or a directory
+//! when ending in `/`. An entry prefixed with `owner/repo:` applies to that
+//! repository only, and later entries take precedence, e.g.
+//! `docs/api/=code, octo-org/octo-repo:schemas/=generated`.
+
+use serde::{Deserialize, Serialize};
+use std::env;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    Code,
+    Test,
+    Script,
+    Docs,
+    Config,
+    Generated,
+    /// Images, data files and whatever else the rules don't know.
+    Other,
+}
+
+impl FileKind {
+    pub const ALL: [FileKind; 7] = [
+        FileKind::Code,
+        FileKind::Test,
+        FileKind::Script,
+        FileKind::Docs,
+        FileKind::Config,
+        FileKind::Generated,
+        FileKind::Other,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileKind::Code => "code",
+            FileKind::Test => "test",
+            FileKind::Script => "script",
+            FileKind::Docs => "docs",
+            FileKind::Config => "config",
+            FileKind::Generated => "generated",
+            FileKind::Other => "other",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<FileKind> {
+        let name = name.trim().to_lowercase();
+        let name = name.strip_suffix('s').unwrap_or(&name);
+        FileKind::ALL
+            .into_iter()
+            .find(|kind| kind.name().trim_end_matches('s') == name)
+    }
+}
+
+const GENERATED_FILES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    "poetry.lock",
+    "Pipfile.lock",
+    "uv.lock",
+    "Gemfile.lock",
+    "composer.lock",
+    "go.sum",
+    "flake.lock",
+    "mix.lock",
+    "pubspec.lock",
+];
+
+const GENERATED_DIRS: &[&str] = &[
+    "vendor/",
+    "node_modules/",
+    "third_party/",
+    "dist/",
+    "target/",
+    "__generated__/",
+];
+
+const GENERATED_SUFFIXES: &[&str] = &[
+    ".min.js",
+    ".min.css",
+    ".map",
+    ".pb.go",
+    "_pb2.py",
+    "_pb2_grpc.py",
+    ".pb.cc",
+    ".pb.h",
+    ".g.dart",
+    ".freezed.dart",
+    ".designer.cs",
+    ".snap",
+    ".lock",
+];
+
+/// Markers tools put at the top of the files they write.
+const GENERATED_MARKERS: &[&str] = &[
+    "@generated",
+    "Code generated by",
+    "DO NOT EDIT",
+    "auto-generated",
+    "autogenerated",
+];
+
+const TEST_DIRS: &[&str] = &[
+    "tests/",
+    "test/",
+    "__tests__/",
+    "spec/",
+    "testdata/",
+    "fixtures/",
+];
+
+const TEST_SUFFIXES: &[&str] = &[
+    "_test.go",
+    "_test.rs",
+    "_test.py",
+    "_spec.rb",
+    ".test.js",
+    ".test.ts",
+    ".test.jsx",
+    ".test.tsx",
+    ".spec.js",
+    ".spec.ts",
+    "Test.java",
+    "Tests.cs",
+];
+
+const DOCS_NAMES: &[&str] = &[
+    "README",
+    "CHANGELOG",
+    "CHANGES",
+    "CONTRIBUTING",
+    "LICENSE",
+    "COPYING",
+    "AUTHORS",
+    "NOTICE",
+    "CODE_OF_CONDUCT",
+];
+
+const DOCS_DIRS: &[&str] = &["docs/", "doc/", "documentation/", "book/"];
+
+const DOCS_EXTENSIONS: &[&str] = &["md", "mdx", "rst", "adoc", "txt", "tex"];
+
+const SCRIPT_NAMES: &[&str] = &["Makefile", "Justfile", "Rakefile", "build.rs"];
+
+const SCRIPT_DIRS: &[&str] = &["scripts/", "script/", "bin/", "tools/"];
+
+const SCRIPT_EXTENSIONS: &[&str] = &["sh", "bash", "zsh", "fish", "ps1", "bat", "cmd", "mk"];
+
+const CONFIG_NAMES: &[&str] = &[
+    "Dockerfile",
+    "Containerfile",
+    "docker-compose.yml",
+    ".gitignore",
+    ".gitattributes",
+    ".dockerignore",
+    ".editorconfig",
+    "CODEOWNERS",
+];
+
+const CONFIG_DIRS: &[&str] = &[".github/", ".circleci/", ".cargo/", ".vscode/"];
+
+const CONFIG_EXTENSIONS: &[&str] = &[
+    "toml",
+    "yaml",
+    "yml",
+    "json",
+    "ini",
+    "cfg",
+    "conf",
+    "env",
+    "properties",
+    "xml",
+    "plist",
+];
+
+const CODE_EXTENSIONS: &[&str] = &[
+    "rs", "go", "py", "pyi", "js", "mjs", "cjs", "ts", "jsx", "tsx", "java", "kt", "kts", "scala",
+    "c", "h", "cc", "cpp", "cxx", "hpp", "cs", "fs", "rb", "php", "swift", "m", "mm", "dart", "ex",
+    "exs", "erl", "hs", "ml", "clj", "lua", "r", "jl", "zig", "nim", "sol", "sql", "proto",
+    "graphql", "vue", "svelte", "html", "css", "scss", "sass", "less", "wasm", "wat",
+];
+
+/// Classifies files by the built-in rules and the `file_kinds` overrides.
+#[derive(Debug, Clone, Default)]
+pub struct Classifier {
+    overrides: Vec<(String, FileKind)>,
+}
+
+impl Classifier {
+    /// The classifier for `owner/repo`, with the `file_kinds` overrides.
+    pub fn from_env(owner: &str, repo: &str) -> Classifier {
+        match env::var("file_kinds") {
+            Ok(entries) => Classifier::from_overrides(&entries, owner, repo),
+            Err(_) => Classifier::default(),
+        }
+    }
+
+    /// The classifier for `owner/repo`, with the overrides in `entries`, in
+    /// the form of `file_kinds`, that apply to all repositories and those for
+    /// this one. Entries that don't parse are logged and skipped.
+    pub fn from_overrides(entries: &str, owner: &str, repo: &str) -> Classifier {
+        let mut classifier = Classifier::default();
+        let this_repo = format!("{owner}/{repo}").to_lowercase();
+        for entry in entries
+            .split(',')
+            .map(|e| e.trim())
+            .filter(|e| !e.is_empty())
+        {
+            let (scope, rule) = match entry.split_once(':') {
+                Some((scope, rule)) => (Some(scope.trim().to_lowercase()), rule),
+                None => (None, entry),
+            };
+            if scope.is_some_and(|scope| scope != this_repo) {
+                continue;
+            }
+            match rule
+                .split_once('=')
+                .and_then(|(pattern, kind)| Some((pattern.trim(), FileKind::from_name(kind)?)))
+            {
+                Some((pattern, kind)) if !pattern.is_empty() => {
+                    classifier = classifier.with_override(pattern, kind)
+                }
+                _ => log::error!("Ignoring file_kinds entry '{entry}'"),
+            }
+        }
+        classifier
+    }
+
+    /// Identifies the overrides, for cache keys. Empty without any.
+    pub fn cache_tag(&self) -> String {
+        self.overrides
+            .iter()
+            .map(|(pattern, kind)| format!("{pattern}={}", kind.name()))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Adds an override, taking precedence over those added before.
+    pub fn with_override(mut self, pattern: &str, kind: FileKind) -> Classifier {
+        self.overrides.insert(0, (pattern.to_string(), kind));
+        self
+    }
+
+    /// The kind of the file at `path`, whose diff, possibly empty, is
+    /// `diff`.
+    pub fn classify(&self, path: &str, diff: &str) -> FileKind {
+        if let Some((_, kind)) = self
+            .overrides
+            .iter()
+            .find(|(pattern, _)| path_matches(pattern, path))
+        {
+            return *kind;
+        }
+        // the marker is in the first lines of a generated file, so only a
+        // hunk at the top of the file can show it
+        let at_top = diff.starts_with("@@ -0,0 +1") || diff.starts_with("@@ -1,");
+        let head: String = diff.lines().take(12).collect::<Vec<_>>().join("\n");
+        if at_top && GENERATED_MARKERS.iter().any(|marker| head.contains(marker)) {
+            return FileKind::Generated;
+        }
+        builtin_kind(path)
+    }
+}
+
+fn builtin_kind(path: &str) -> FileKind {
+    let file = path.rsplit('/').next().unwrap_or(path);
+    let stem = file.split('.').next().unwrap_or(file);
+    let extension = match file.rsplit_once('.') {
+        Some((_, ext)) if !stem.is_empty() => ext.to_lowercase(),
+        _ => String::new(),
+    };
+    let in_dir = |dirs: &[&str]| dirs.iter().any(|dir| path_matches(dir, path));
+
+    if GENERATED_FILES.contains(&file)
+        || in_dir(GENERATED_DIRS)
+        || GENERATED_SUFFIXES.iter().any(|s| file.ends_with(s))
+    {
+        FileKind::Generated
+    } else if in_dir(TEST_DIRS)
+        || TEST_SUFFIXES.iter().any(|s| file.ends_with(s))
+        || (file.starts_with("test_") && extension == "py")
+    {
+        FileKind::Test
+    } else if DOCS_NAMES.contains(&stem)
+        || DOCS_EXTENSIONS.contains(&extension.as_str())
+        || in_dir(DOCS_DIRS) && !CODE_EXTENSIONS.contains(&extension.as_str())
+    {
+        FileKind::Docs
+    } else if SCRIPT_NAMES.contains(&file)
+        || SCRIPT_EXTENSIONS.contains(&extension.as_str())
+        || in_dir(SCRIPT_DIRS) && !CODE_EXTENSIONS.contains(&extension.as_str())
+    {
+        FileKind::Script
+    } else if CONFIG_NAMES.contains(&file)
+        || CONFIG_EXTENSIONS.contains(&extension.as_str())
+        || in_dir(CONFIG_DIRS)
+        || file.starts_with(".env")
+    {
+        FileKind::Config
+    } else if CODE_EXTENSIONS.contains(&extension.as_str()) {
+        FileKind::Code
+    } else {
+        FileKind::Other
+    }
+}
+
+/// A directory pattern, ending in `/`, matches anything under a directory
+/// of that name; any other pattern matches the end of the path.
+fn path_matches(pattern: &str, path: &str) -> bool {
+    match pattern.strip_suffix('/') {
+        Some(dir) => {
+            let dir = dir.trim_start_matches('/');
+            path.starts_with(&format!("{dir}/")) || path.contains(&format!("/{dir}/"))
+        }
+        None => path.ends_with(pattern),
+    }
+}
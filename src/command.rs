@@ -0,0 +1,575 @@
+//! Parsing of the chat commands understood by the bot.
+//!
+//! Everything after the trigger word is split into shell-like tokens (double,
+//! single and Slack's curly quotes group words together) and read as
+//!
+//! ```text
+//! <subcommand> <owner>/<repo> <user> [--since <date>] [--until <date>] [--range <period>] [--host <host>] [--role <roles>]
+//! ```
+//!
+//! Dates and periods use the forms accepted by [`DateRange::parse`]. `--host`
+//! points a single command at one of the GitHub Enterprise Server instances
+//! listed in `github_enterprise_hosts`. `--role` keeps only the issues and
+//! pull requests where the user had one of the comma separated [`Role`]s.
+//!
+//! The subcommand may be left out when the repository is given as a slug, and
+//! the old `<owner> <repo> <user>` form is still accepted for `contrib`.
+
+use crate::date_range::DateRange;
+use crate::roles::Role;
+use chrono::{NaiveDate, Utc};
+use std::fmt;
+
+/// The help text shown for `help` and after every malformed command.
+pub fn usage(trigger_word: &str) -> String {
+    format!(
+        "Usage: {trigger_word} <subcommand> <owner>/<repo> <user> [--since <date>] [--until <date>] [--range <period>] [--host <host>] [--role <roles>]
+Subcommands:
+  contrib   correlate the user's commits and issues into a contribution report (default)
+  commits   summarize the user's commits only
+  issues    summarize the issues and pull requests the user is involved in only
+  reviews   summarize the user's pull requests and the code reviews they gave
+  help      show this message
+Dates and periods: 2026-01-01, 2026-03, 2026, \"Q3 2026\", \"last 7 days\", 30d, \"this month\", 2026-01..2026-03
+Host: a GitHub Enterprise Server the bot is configured for, e.g. --host github.example.com (default: the bot's configured GitHub)
+Roles: author, assignee, commenter, mentioned, closer, e.g. --role author,closer (default: all)
+Example: {trigger_word} contrib WasmEdge/WasmEdge juntao --range \"last 2 weeks\""
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Contrib(ReportArgs),
+    Commits(ReportArgs),
+    Issues(ReportArgs),
+    Reviews(ReportArgs),
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportArgs {
+    pub owner: String,
+    pub repo: String,
+    pub user: String,
+    pub range: DateRange,
+    /// A GitHub Enterprise Server host overriding the configured one for this command.
+    pub host: Option<String>,
+    /// The roles an issue must have the user in to be reported, any if empty.
+    pub roles: Vec<Role>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    Empty,
+    UnterminatedQuote,
+    UnknownSubcommand(String),
+    MissingRepo,
+    InvalidRepo(String),
+    MissingUser,
+    UnexpectedArgument(String),
+    UnknownFlag(String),
+    MissingFlagValue(String),
+    DuplicateFlag(String),
+    ConflictingFlags(String, String),
+    InvalidDate(String),
+    EmptyRange(DateRange),
+    InvalidHost(String),
+    InvalidRole(String),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::Empty => write!(f, "no command given"),
+            CommandError::UnterminatedQuote => {
+                write!(f, "a quoted argument is missing its closing quote")
+            }
+            CommandError::UnknownSubcommand(s) => write!(f, "unknown subcommand '{s}'"),
+            CommandError::MissingRepo => {
+                write!(f, "missing the repository, expected <owner>/<repo>")
+            }
+            CommandError::InvalidRepo(s) => {
+                write!(f, "'{s}' is not a repository, expected <owner>/<repo>")
+            }
+            CommandError::MissingUser => write!(f, "missing the GitHub user name"),
+            CommandError::UnexpectedArgument(s) => write!(f, "unexpected argument '{s}'"),
+            CommandError::UnknownFlag(s) => write!(f, "unknown flag '--{s}'"),
+            CommandError::MissingFlagValue(s) => write!(f, "flag '--{s}' expects a value"),
+            CommandError::DuplicateFlag(s) => write!(f, "flag '--{s}' is given more than once"),
+            CommandError::ConflictingFlags(a, b) => {
+                write!(f, "flags '--{a}' and '--{b}' cannot be used together")
+            }
+            CommandError::InvalidDate(s) => write!(
+                f,
+                "'{s}' is not a date or period, try 2026-01-01, 2026-03, \"Q3 2026\" or \"last 7 days\""
+            ),
+            CommandError::EmptyRange(range) => write!(f, "the period {range} is empty"),
+            CommandError::InvalidHost(s) => {
+                write!(f, "'{s}' is not a host, expected e.g. github.example.com")
+            }
+            CommandError::InvalidRole(s) => write!(
+                f,
+                "'{s}' is not a role, expected author, assignee, commenter, mentioned or closer"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl Command {
+    /// The `--host` given with the command, if any.
+    pub fn host(&self) -> Option<&str> {
+        match self {
+            Command::Contrib(args)
+            | Command::Commits(args)
+            | Command::Issues(args)
+            | Command::Reviews(args) => args.host.as_deref(),
+            Command::Help => None,
+        }
+    }
+
+    /// Parses the text following the trigger word.
+    pub fn parse(input: &str) -> Result<Command, CommandError> {
+        Command::parse_on(input, Utc::now().date_naive())
+    }
+
+    /// Like [`Command::parse`], with relative periods resolved against `today`.
+    pub fn parse_on(input: &str, today: NaiveDate) -> Result<Command, CommandError> {
+        let mut positionals = Vec::new();
+        let mut range = DateRange::default();
+        let mut host = None;
+        let mut roles = Vec::new();
+        let mut seen_flags: Vec<String> = Vec::new();
+
+        let mut tokens = tokenize(input)?.into_iter();
+        while let Some(token) = tokens.next() {
+            let Some(flag) = token.strip_prefix("--") else {
+                positionals.push(token);
+                continue;
+            };
+            let (name, inline_value) = match flag.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (flag.to_string(), None),
+            };
+            if !matches!(name.as_str(), "since" | "until" | "range" | "host" | "role") {
+                return Err(CommandError::UnknownFlag(name));
+            }
+            if seen_flags.contains(&name) {
+                return Err(CommandError::DuplicateFlag(name));
+            }
+            if let Some(other) = seen_flags.iter().find(|seen| {
+                is_date_flag(&name)
+                    && is_date_flag(seen)
+                    && (name == "range" || seen.as_str() == "range")
+            }) {
+                return Err(CommandError::ConflictingFlags(other.clone(), name));
+            }
+
+            let value = match inline_value.or_else(|| tokens.next()) {
+                Some(value) if !value.is_empty() => value,
+                _ => return Err(CommandError::MissingFlagValue(name)),
+            };
+            if name == "host" {
+                host = Some(parse_host(&value)?);
+                seen_flags.push(name);
+                continue;
+            }
+            if name == "role" {
+                roles = parse_roles(&value)?;
+                seen_flags.push(name);
+                continue;
+            }
+            let Some(period) = DateRange::parse(&value, today) else {
+                return Err(CommandError::InvalidDate(value));
+            };
+            match name.as_str() {
+                "since" => range.since = period.since,
+                "until" => range.until = period.until,
+                _ => range = period,
+            }
+            seen_flags.push(name);
+        }
+        if range.is_empty() {
+            return Err(CommandError::EmptyRange(range));
+        }
+
+        let mut positionals = positionals.into_iter().peekable();
+        let subcommand = match positionals.peek() {
+            None => return Err(CommandError::Empty),
+            Some(first) => first.to_lowercase(),
+        };
+        let rest: Vec<String> = match is_known_subcommand(&subcommand) {
+            true => positionals.skip(1).collect(),
+            false => positionals.collect(),
+        };
+
+        if subcommand == "help" {
+            return Ok(Command::Help);
+        }
+
+        let is_contrib = subcommand == "contrib" || !is_known_subcommand(&subcommand);
+        let (owner, repo, user) = match rest.as_slice() {
+            [] => return Err(CommandError::MissingRepo),
+            [slug, tail @ ..] if slug.contains('/') => {
+                let (owner, repo) = parse_slug(slug)?;
+                match tail {
+                    [] => return Err(CommandError::MissingUser),
+                    [user] => (owner, repo, user.clone()),
+                    [_, extra, ..] => return Err(CommandError::UnexpectedArgument(extra.clone())),
+                }
+            }
+            // legacy form of `contrib`: `<owner> <repo> <user>`
+            [owner, repo, user] if is_contrib && !repo.contains('/') && !user.contains('/') => {
+                (owner.clone(), repo.clone(), user.clone())
+            }
+            [first, ..] if !is_known_subcommand(&subcommand) => {
+                return Err(CommandError::UnknownSubcommand(first.clone()))
+            }
+            [repo, ..] => return Err(CommandError::InvalidRepo(repo.clone())),
+        };
+
+        let args = ReportArgs {
+            owner,
+            repo,
+            user,
+            range,
+            host,
+            roles,
+        };
+
+        Ok(match subcommand.as_str() {
+            "commits" => Command::Commits(args),
+            "issues" => Command::Issues(args),
+            "reviews" => Command::Reviews(args),
+            _ => Command::Contrib(args),
+        })
+    }
+}
+
+fn is_known_subcommand(word: &str) -> bool {
+    matches!(word, "contrib" | "commits" | "issues" | "reviews" | "help")
+}
+
+fn is_date_flag(name: &str) -> bool {
+    matches!(name, "since" | "until" | "range")
+}
+
+/// Accepts a bare host, optionally with a port, or a base URL such as
+/// `https://github.example.com/`. Returns the URL without a trailing slash.
+///
+/// Slack sends a domain as a link, `<http://github.example.com|github.example.com>`,
+/// whose scheme is Slack's guess, so the text shown is taken instead.
+fn parse_host(value: &str) -> Result<String, CommandError> {
+    let trimmed = value.trim();
+    let trimmed = match trimmed
+        .strip_prefix('<')
+        .and_then(|link| link.strip_suffix('>'))
+    {
+        Some(link) => link.rsplit_once('|').map_or(link, |(_, text)| text),
+        None => trimmed,
+    };
+    let trimmed = trimmed.trim().trim_end_matches('/');
+    let (scheme, host) = match trimmed.split_once("://") {
+        Some((scheme @ ("http" | "https"), host)) => (scheme, host),
+        Some(_) => return Err(CommandError::InvalidHost(value.to_string())),
+        None => ("https", trimmed),
+    };
+    let valid = !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':'));
+    match valid {
+        true => Ok(format!("{scheme}://{host}")),
+        false => Err(CommandError::InvalidHost(value.to_string())),
+    }
+}
+
+/// A comma separated list of roles, e.g. `author,closer`.
+fn parse_roles(value: &str) -> Result<Vec<Role>, CommandError> {
+    let mut roles = Vec::new();
+    for name in value.split(',').filter(|name| !name.trim().is_empty()) {
+        match Role::from_name(name) {
+            Some(role) if !roles.contains(&role) => roles.push(role),
+            Some(_) => {}
+            None => return Err(CommandError::InvalidRole(name.trim().to_string())),
+        }
+    }
+    match roles.is_empty() {
+        true => Err(CommandError::InvalidRole(value.to_string())),
+        false => Ok(roles),
+    }
+}
+
+fn parse_slug(slug: &str) -> Result<(String, String), CommandError> {
+    let slug = slug.trim_end_matches('/');
+    match slug.split_once('/') {
+        Some((owner, repo)) if !owner.is_empty() && !repo.is_empty() && !repo.contains('/') => {
+            Ok((owner.to_string(), repo.to_string()))
+        }
+        _ => Err(CommandError::InvalidRepo(slug.to_string())),
+    }
+}
+
+/// Splits `input` on whitespace, keeping quoted runs together. A backslash
+/// escapes the next character outside of single quotes.
+fn tokenize(input: &str) -> Result<Vec<String>, CommandError> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                in_token = true;
+            }
+            '"' | '\'' | '\u{201c}' | '\u{2018}' => {
+                let closing = match c {
+                    '\u{201c}' => '\u{201d}',
+                    '\u{2018}' => '\u{2019}',
+                    _ => c,
+                };
+                loop {
+                    match chars.next() {
+                        None => return Err(CommandError::UnterminatedQuote),
+                        Some(q) if q == closing => break,
+                        Some('\\') if c == '"' => {
+                            if let Some(next) = chars.next() {
+                                current.push(next);
+                            }
+                        }
+                        Some(q) => current.push(q),
+                    }
+                }
+                in_token = true;
+            }
+            c => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if in_token {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, 17).unwrap()
+    }
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn args(owner: &str, repo: &str, user: &str, range: DateRange) -> ReportArgs {
+        ReportArgs {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            user: user.to_string(),
+            range,
+            host: None,
+            roles: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn commands_parse() {
+        let all_time = DateRange::default();
+        let cases = [
+            (
+                "contrib octo-org/octo-repo alice",
+                Command::Contrib(args("octo-org", "octo-repo", "alice", all_time)),
+            ),
+            // the subcommand defaults to contrib
+            (
+                "octo-org/octo-repo alice",
+                Command::Contrib(args("octo-org", "octo-repo", "alice", all_time)),
+            ),
+            // the legacy positional form
+            (
+                "octo-org octo-repo alice",
+                Command::Contrib(args("octo-org", "octo-repo", "alice", all_time)),
+            ),
+            (
+                "contrib octo-org octo-repo alice",
+                Command::Contrib(args("octo-org", "octo-repo", "alice", all_time)),
+            ),
+            (
+                "ISSUES octo-org/octo-repo/ alice",
+                Command::Issues(args("octo-org", "octo-repo", "alice", all_time)),
+            ),
+            (
+                "commits octo-org/octo-repo alice --range \"last 7 days\"",
+                Command::Commits(args(
+                    "octo-org",
+                    "octo-repo",
+                    "alice",
+                    DateRange::between(day(2026, 10, 11), today()),
+                )),
+            ),
+            // Slack turns straight quotes into curly ones
+            (
+                "reviews octo-org/octo-repo alice --range \u{201c}Q3 2026\u{201d}",
+                Command::Reviews(args(
+                    "octo-org",
+                    "octo-repo",
+                    "alice",
+                    DateRange::between(day(2026, 7, 1), day(2026, 9, 30)),
+                )),
+            ),
+            (
+                "contrib 'octo-org/octo-repo' alice --since=2026-09 --until 2026-09-15",
+                Command::Contrib(args(
+                    "octo-org",
+                    "octo-repo",
+                    "alice",
+                    DateRange::between(day(2026, 9, 1), day(2026, 9, 15)),
+                )),
+            ),
+            ("help", Command::Help),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(Command::parse_on(input, today()), Ok(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn flags_parse() {
+        let Ok(Command::Issues(parsed)) = Command::parse_on(
+            "issues octo-org/octo-repo alice --host ghe.example.com --role author,Closers",
+            today(),
+        ) else {
+            panic!("not an issues command");
+        };
+        assert_eq!(parsed.host.as_deref(), Some("https://ghe.example.com"));
+        assert_eq!(parsed.roles, [Role::Author, Role::Closer]);
+    }
+
+    #[test]
+    fn hosts_parse_from_slack_links() {
+        let cases = [
+            ("ghe.example.com", "https://ghe.example.com"),
+            (
+                "http://ghe.example.com:8080/",
+                "http://ghe.example.com:8080",
+            ),
+            (
+                "<http://ghe.example.com|ghe.example.com>",
+                "https://ghe.example.com",
+            ),
+            (
+                "<http://ghe.example.com|http://ghe.example.com>",
+                "http://ghe.example.com",
+            ),
+            ("<https://ghe.example.com/>", "https://ghe.example.com"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_host(value), Ok(expected.to_string()), "{value}");
+        }
+        assert_eq!(
+            parse_host("<mailto:a@example.com|a@example.com>"),
+            Err(CommandError::InvalidHost(
+                "<mailto:a@example.com|a@example.com>".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn malformed_commands_are_rejected() {
+        let cases = [
+            ("", CommandError::Empty),
+            (
+                "contrib octo-org/octo-repo \"alice",
+                CommandError::UnterminatedQuote,
+            ),
+            ("contrib", CommandError::MissingRepo),
+            ("contrib octo-org/octo-repo", CommandError::MissingUser),
+            (
+                "contrib octo-org alice",
+                CommandError::InvalidRepo("octo-org".to_string()),
+            ),
+            (
+                "summarize octo-org/octo-repo alice",
+                CommandError::UnknownSubcommand("summarize".to_string()),
+            ),
+            (
+                "contrib octo-org/octo-repo alice bob",
+                CommandError::UnexpectedArgument("bob".to_string()),
+            ),
+            // the legacy positional form is only kept for contrib
+            (
+                "commits octo-org octo-repo alice",
+                CommandError::InvalidRepo("octo-org".to_string()),
+            ),
+            (
+                "contrib octo-org/octo-repo alice --verbose yes",
+                CommandError::UnknownFlag("verbose".to_string()),
+            ),
+            (
+                "contrib octo-org/octo-repo alice --range",
+                CommandError::MissingFlagValue("range".to_string()),
+            ),
+            (
+                "contrib octo-org/octo-repo alice --since 2026 --since 2025",
+                CommandError::DuplicateFlag("since".to_string()),
+            ),
+            (
+                "contrib octo-org/octo-repo alice --since 2026-01 --range 2026",
+                CommandError::ConflictingFlags("since".to_string(), "range".to_string()),
+            ),
+            (
+                "contrib octo-org/octo-repo alice --range someday",
+                CommandError::InvalidDate("someday".to_string()),
+            ),
+            (
+                "contrib octo-org/octo-repo alice --since 2026-03 --until 2026-01",
+                CommandError::EmptyRange(DateRange::between(day(2026, 3, 1), day(2026, 1, 31))),
+            ),
+            (
+                "contrib octo-org/octo-repo alice --host ftp://ghe",
+                CommandError::InvalidHost("ftp://ghe".to_string()),
+            ),
+            (
+                "contrib octo-org/octo-repo alice --role reviewer",
+                CommandError::InvalidRole("reviewer".to_string()),
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(Command::parse_on(input, today()), Err(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn usage_lists_every_subcommand_and_flag() {
+        let text = usage("bot@get");
+        assert!(text.starts_with("Usage: bot@get <subcommand> <owner>/<repo> <user>"));
+        for word in ["contrib", "commits", "issues", "reviews", "help"] {
+            assert!(is_known_subcommand(word));
+            assert!(text.contains(&format!("\n  {word} ")), "{word}");
+        }
+        for flag in ["--since", "--until", "--range", "--host", "--role"] {
+            assert!(text.contains(flag), "{flag}");
+        }
+        assert_eq!(
+            CommandError::MissingUser.to_string(),
+            "missing the GitHub user name"
+        );
+    }
+}
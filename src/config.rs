@@ -0,0 +1,193 @@
+//! Settings read from the flow's environment variables.
+
+use crate::slack::SlackSink;
+use crate::tokens;
+use std::env;
+
+/// Where the intermediate commit and issue summaries of a report are posted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugOutput {
+    /// Only the final report is posted.
+    Off,
+    /// Posted to the channel the command came from, ahead of the final report.
+    Channel,
+    /// Posted to a separate channel, e.g. one used for tuning the prompts.
+    Side { workspace: String, channel: String },
+}
+
+impl DebugOutput {
+    /// Reads `debug_output`, one of `off` (default), `channel` or `side`. The
+    /// side channel is named by `debug_channel` and lives in `debug_workspace`,
+    /// which defaults to the workspace the bot listens to.
+    pub fn from_env(workspace: &str) -> DebugOutput {
+        let mode = env::var("debug_output").unwrap_or("off".to_string());
+        match mode.trim().to_lowercase().as_str() {
+            "channel" => DebugOutput::Channel,
+            "side" => match env::var("debug_channel") {
+                Ok(channel) if !channel.is_empty() => DebugOutput::Side {
+                    workspace: env::var("debug_workspace").unwrap_or(workspace.to_string()),
+                    channel,
+                },
+                _ => {
+                    log::error!("debug_output is 'side' but debug_channel is not set");
+                    DebugOutput::Off
+                }
+            },
+            "off" | "" => DebugOutput::Off,
+            other => {
+                log::error!("Unknown debug_output '{other}', intermediate output is off");
+                DebugOutput::Off
+            }
+        }
+    }
+
+    /// Posts an intermediate result, labelled with the pipeline stage it came from.
+    pub async fn post(
+        &self,
+        slack: &dyn SlackSink,
+        workspace: &str,
+        channel: &str,
+        label: &str,
+        text: &str,
+    ) {
+        if text.trim().is_empty() {
+            return;
+        }
+        let message = format!("[{label}]\n{text}");
+        match self {
+            DebugOutput::Off => {}
+            DebugOutput::Channel => slack.send(workspace, channel, message).await,
+            DebugOutput::Side { workspace, channel } => {
+                slack.send(workspace, channel, message).await
+            }
+        }
+    }
+}
+
+/// The summarization steps of the pipeline, each configured on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// One summary per commit.
+    Commit,
+    /// One summary per issue.
+    Issue,
+    /// One summary per pull request authored or reviewed.
+    Review,
+    /// The final report correlating commits and issues.
+    Correlation,
+    /// Condensing batches of summaries that don't fit the final report.
+    Digest,
+}
+
+impl Stage {
+    /// The prefix of the stage's environment variables.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Commit => "commit",
+            Stage::Issue => "issue",
+            Stage::Review => "review",
+            Stage::Correlation => "correlation",
+            Stage::Digest => "digest",
+        }
+    }
+}
+
+/// How a stage prompts the model. Each stage is a two step chat: the first
+/// step extracts the key facts, the second writes the summary from them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StageConfig {
+    /// Overrides `llm_model` for this stage. With `openai_flows` it has to be
+    /// one of [`crate::llm::FLOWS_MODELS`].
+    pub model: Option<String>,
+    pub temperature: f32,
+    /// Generation limit of the extraction step.
+    pub extract_tokens: u16,
+    /// Generation limit of the summary, also quoted in the prompt.
+    pub summary_tokens: u16,
+    /// Context window of the stage's model in tokens.
+    pub context_window: usize,
+}
+
+impl StageConfig {
+    /// Reads `<stage>_model`, `<stage>_temperature`, `<stage>_extract_tokens`,
+    /// `<stage>_summary_tokens` and `<stage>_context_window`, where `<stage>`
+    /// is `commit`, `issue`, `review`, `correlation` or `digest`. Unset or
+    /// malformed values keep the defaults, except for a model the provider
+    /// doesn't offer, which fails the stage's requests. The context window otherwise comes
+    /// from `llm_context_window`, or is looked up by model name.
+    pub fn from_env(stage: Stage) -> StageConfig {
+        let mut config = StageConfig::default_for(stage);
+        let var = |key: &str| {
+            env::var(format!("{}_{key}", stage.name()))
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        if let Some(model) = var("model") {
+            config.model = Some(model);
+        }
+        if let Some(temperature) = var("temperature").and_then(|v| v.parse().ok()) {
+            config.temperature = temperature;
+        }
+        if let Some(n) = var("extract_tokens").and_then(|v| v.parse().ok()) {
+            config.extract_tokens = n;
+        }
+        if let Some(n) = var("summary_tokens").and_then(|v| v.parse().ok()) {
+            config.summary_tokens = n;
+        }
+
+        let model = config.model_name();
+        let configured_window = var("context_window")
+            .or(env::var("llm_context_window").ok())
+            .and_then(|v| v.trim().parse().ok());
+        if let Some(window) = configured_window.or(tokens::context_window(&model)) {
+            config.context_window = window;
+        }
+        config
+    }
+
+    pub fn default_for(stage: Stage) -> StageConfig {
+        let (extract_tokens, summary_tokens) = match stage {
+            Stage::Commit | Stage::Issue | Stage::Review => (256, 128),
+            Stage::Correlation | Stage::Digest => (512, 256),
+        };
+        StageConfig {
+            model: None,
+            temperature: 0.7,
+            extract_tokens,
+            summary_tokens,
+            context_window: 16_385,
+        }
+    }
+
+    /// The stage's model, or else `llm_model`, or else the default model.
+    pub fn model_name(&self) -> String {
+        self.model
+            .clone()
+            .or(env::var("llm_model").ok())
+            .unwrap_or("gpt-3.5-turbo-16k".to_string())
+    }
+
+    /// Identifies the settings that shape a summary, for cache keys.
+    pub fn cache_tag(&self) -> String {
+        format!(
+            "{}-t{}-{}-{}",
+            self.model_name(),
+            self.temperature,
+            self.extract_tokens,
+            self.summary_tokens
+        )
+    }
+
+    /// How many tokens of GitHub data fit in the stage's prompt, leaving room
+    /// for the instructions and both generated replies.
+    pub fn data_budget(&self) -> usize {
+        // the longest instructions of the prompts, with some slack
+        const INSTRUCTIONS: usize = 1_000;
+        self.context_window
+            .saturating_sub(self.extract_tokens as usize)
+            .saturating_sub(self.summary_tokens as usize)
+            .saturating_sub(INSTRUCTIONS)
+            .max(256)
+    }
+}
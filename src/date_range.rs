@@ -0,0 +1,211 @@
+//! Time windows for reports, e.g. `2026-01-01..2026-03-31`, `Q3 2026` or `last 7 days`.
+
+use chrono::{Datelike, Days, Months, NaiveDate};
+use std::fmt;
+
+/// An inclusive range of days, open on either end when the bound is `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DateRange {
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+}
+
+impl DateRange {
+    /// Parses a period relative to `today`. Accepted forms:
+    ///
+    /// * a day, month or year: `2026-03-01`, `2026-03`, `2026`
+    /// * a quarter: `Q3 2026`, `2026-Q3`
+    /// * a rolling window ending today: `last 7 days`, `past 2 weeks`, `last month`, `30d`
+    /// * a calendar period so far: `today`, `yesterday`, `this week`, `this month`,
+    ///   `this quarter`, `this year`
+    /// * two of the above joined by `..`, either side may be empty: `2026-01..2026-03`
+    pub fn parse(expr: &str, today: NaiveDate) -> Option<DateRange> {
+        let expr = expr.trim().to_lowercase();
+        if let Some((start, end)) = expr.split_once("..") {
+            let since = match start.trim() {
+                "" => None,
+                start => DateRange::parse(start, today)?.since,
+            };
+            let until = match end.trim() {
+                "" => None,
+                end => DateRange::parse(end, today)?.until,
+            };
+            return Some(DateRange { since, until });
+        }
+
+        let words: Vec<&str> = expr.split_whitespace().collect();
+        match words.as_slice() {
+            [] => None,
+            ["today"] => Some(DateRange::between(today, today)),
+            ["yesterday"] => {
+                let day = today.checked_sub_days(Days::new(1))?;
+                Some(DateRange::between(day, day))
+            }
+            ["this", unit] => {
+                let start = match *unit {
+                    "week" => today.checked_sub_days(Days::new(
+                        today.weekday().num_days_from_monday() as u64,
+                    ))?,
+                    "month" => today.with_day(1)?,
+                    "quarter" => {
+                        NaiveDate::from_ymd_opt(today.year(), quarter_start(today.month()), 1)?
+                    }
+                    "year" => NaiveDate::from_ymd_opt(today.year(), 1, 1)?,
+                    _ => return None,
+                };
+                Some(DateRange::between(start, today))
+            }
+            ["last" | "past", unit] => rolling(1, unit, today),
+            ["last" | "past", count, unit] => rolling(count.parse().ok()?, unit, today),
+            [single] => parse_single(single, today),
+            [a, b] => parse_quarter(&format!("{a}{b}")),
+            _ => None,
+        }
+    }
+
+    pub fn between(since: NaiveDate, until: NaiveDate) -> DateRange {
+        DateRange {
+            since: Some(since),
+            until: Some(until),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!((self.since, self.until), (Some(since), Some(until)) if since > until)
+    }
+
+    pub fn contains(&self, day: NaiveDate) -> bool {
+        self.since.is_none_or(|since| since <= day) && self.until.is_none_or(|until| day <= until)
+    }
+
+    /// `since`/`until` parameters for the commits API, which takes timestamps.
+    pub fn commits_params(&self) -> String {
+        let mut params = String::new();
+        if let Some(since) = self.since {
+            params.push_str(&format!("&since={since}T00:00:00Z"));
+        }
+        if let Some(until) = self.until {
+            params.push_str(&format!("&until={until}T23:59:59Z"));
+        }
+        params
+    }
+
+    /// Qualifiers for the issue search API. An issue is part of the window if
+    /// it already existed at the end of it and was still active after its start.
+    pub fn search_qualifiers(&self) -> String {
+        let mut qualifiers = String::new();
+        if let Some(since) = self.since {
+            qualifiers.push_str(&format!(" updated:>={since}"));
+        }
+        if let Some(until) = self.until {
+            qualifiers.push_str(&format!(" created:<={until}"));
+        }
+        qualifiers
+    }
+
+    /// A search qualifier restricting `field`, e.g. `merged`, to the window.
+    pub fn search_qualifier(&self, field: &str) -> String {
+        match (self.since, self.until) {
+            (Some(since), Some(until)) => format!(" {field}:{since}..{until}"),
+            (Some(since), None) => format!(" {field}:>={since}"),
+            (None, Some(until)) => format!(" {field}:<={until}"),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+impl fmt::Display for DateRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.since, self.until) {
+            (None, None) => write!(f, "all time"),
+            (Some(since), None) => write!(f, "since {since}"),
+            (None, Some(until)) => write!(f, "until {until}"),
+            (Some(since), Some(until)) if since == until => write!(f, "on {since}"),
+            (Some(since), Some(until)) => write!(f, "{since} to {until}"),
+        }
+    }
+}
+
+fn quarter_start(month: u32) -> u32 {
+    (month - 1) / 3 * 3 + 1
+}
+
+/// A window of `count` units ending today, or `None` if it would reach past
+/// the dates chrono can represent.
+fn rolling(count: u32, unit: &str, today: NaiveDate) -> Option<DateRange> {
+    if count == 0 {
+        return None;
+    }
+    let since = match unit.trim_end_matches('s') {
+        "day" => today.checked_sub_days(Days::new(count as u64))?,
+        "week" => today.checked_sub_days(Days::new(count as u64 * 7))?,
+        "month" => today.checked_sub_months(Months::new(count))?,
+        "quarter" => today.checked_sub_months(Months::new(count.checked_mul(3)?))?,
+        "year" => today.checked_sub_months(Months::new(count.checked_mul(12)?))?,
+        _ => return None,
+    };
+    // the window includes today, so `last 7 days` is today and the 6 days before it
+    Some(DateRange::between(
+        since.checked_add_days(Days::new(1))?,
+        today,
+    ))
+}
+
+fn parse_single(word: &str, today: NaiveDate) -> Option<DateRange> {
+    if let Ok(day) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+        return Some(DateRange::between(day, day));
+    }
+
+    // `30d`, `2w`, `6m`, `1y`
+    if let Some(unit) = word.chars().last().filter(|c| c.is_ascii_alphabetic()) {
+        if let Ok(count) = word[..word.len() - 1].parse::<u32>() {
+            let unit = match unit {
+                'd' => "day",
+                'w' => "week",
+                'm' => "month",
+                'y' => "year",
+                _ => return None,
+            };
+            return rolling(count, unit, today);
+        }
+    }
+
+    let parts: Vec<&str> = word.split('-').collect();
+    match parts.as_slice() {
+        [year] if year.len() == 4 => {
+            let year = year.parse().ok()?;
+            Some(DateRange::between(
+                NaiveDate::from_ymd_opt(year, 1, 1)?,
+                NaiveDate::from_ymd_opt(year, 12, 31)?,
+            ))
+        }
+        [year, month] if year.len() == 4 && month.len() == 2 && month.parse::<u32>().is_ok() => {
+            let start = NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, 1)?;
+            let end = start
+                .checked_add_months(Months::new(1))?
+                .checked_sub_days(Days::new(1))?;
+            Some(DateRange::between(start, end))
+        }
+        _ => parse_quarter(word),
+    }
+}
+
+/// `q3 2026`, `q3-2026`, `2026q3` and `2026-q3`, with separators already
+/// stripped or not.
+fn parse_quarter(word: &str) -> Option<DateRange> {
+    let compact: String = word.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+    let (quarter, year) = if let Some(rest) = compact.strip_prefix('q') {
+        (rest.get(..1)?, rest.get(1..)?)
+    } else {
+        let (year, quarter) = compact.split_once('q')?;
+        (quarter, year)
+    };
+    let quarter: u32 = quarter.parse().ok().filter(|q| (1..=4).contains(q))?;
+    let year: i32 = year.parse().ok().filter(|_| year.len() == 4)?;
+
+    let start = NaiveDate::from_ymd_opt(year, (quarter - 1) * 3 + 1, 1)?;
+    let end = start
+        .checked_add_months(Months::new(3))?
+        .checked_sub_days(Days::new(1))?;
+    Some(DateRange::between(start, end))
+}
@@ -0,0 +1,99 @@
+//! Errors of the report pipeline. They are shown to the Slack user as is, so
+//! the messages say what went wrong and, where possible, what to do about it.
+
+use std::fmt;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// GitHub answered with a status the bot doesn't handle specially.
+    Http {
+        status: u16,
+        url: String,
+    },
+    /// The token was rejected (401) or lacks access to the resource (403).
+    Auth {
+        status: u16,
+        url: String,
+    },
+    RepoNotFound {
+        owner: String,
+        repo: String,
+    },
+    UserNotFound {
+        user: String,
+    },
+    /// A 404 for anything other than the repository or user themselves.
+    NotFound {
+        url: String,
+    },
+    /// GitHub kept rate limiting the request after the allowed retries.
+    RateLimited {
+        url: String,
+    },
+    /// `--host` named a server the bot has no token for.
+    HostNotAllowed {
+        host: String,
+    },
+    /// The request didn't get a response at all.
+    Transport {
+        url: String,
+        message: String,
+    },
+    /// A response body that doesn't have the expected shape.
+    Json {
+        what: String,
+        message: String,
+    },
+    /// The language model call failed.
+    Llm {
+        tag: String,
+        message: String,
+    },
+    /// The language model answered, but with nothing usable, typically
+    /// because generation was cut off.
+    LlmTruncated {
+        tag: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Http { status, url } => write!(f, "GitHub answered {status} for {url}"),
+            Error::Auth { status, url } => write!(
+                f,
+                "GitHub refused access ({status}) to {url}, check that github_token is valid and can read the repository"
+            ),
+            Error::RepoNotFound { owner, repo } => write!(
+                f,
+                "the repository {owner}/{repo} doesn't exist, or the bot's token can't see it"
+            ),
+            Error::UserNotFound { user } => write!(f, "there is no GitHub user '{user}'"),
+            Error::NotFound { url } => write!(f, "GitHub has nothing at {url}"),
+            Error::RateLimited { url } => write!(
+                f,
+                "GitHub's rate limit was hit while fetching {url}, please try again later"
+            ),
+            Error::HostNotAllowed { host } => write!(
+                f,
+                "{host} is not one of the GitHub hosts the bot is configured for (github_enterprise_hosts)"
+            ),
+            Error::Transport { url, message } => {
+                write!(f, "couldn't reach GitHub for {url}: {message}")
+            }
+            Error::Json { what, message } => {
+                write!(f, "couldn't read GitHub's response for {what}: {message}")
+            }
+            Error::Llm { tag, message } => {
+                write!(f, "the language model failed on {tag}: {message}")
+            }
+            Error::LlmTruncated { tag } => {
+                write!(f, "the language model gave no usable answer on {tag}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,644 @@
+//! Thin client for the GitHub REST API.
+
+use crate::cache::{self, Cache, NoCache};
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use http_req::{request::Method, request::Request, uri::Uri};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// GitHub's search API never returns more than this many results for a query.
+pub const SEARCH_RESULT_LIMIT: usize = 1000;
+
+/// A response as seen by a [`Transport`], whatever its status.
+#[derive(Debug, Clone, Default)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim())
+    }
+}
+
+/// Sends GET requests for the [`GithubClient`]. The default goes over the
+/// network, [`FixtureTransport`] serves canned responses.
+#[async_trait(?Send)]
+pub trait Transport {
+    async fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<HttpResponse>;
+}
+
+/// The network transport, built on `http_req`.
+#[derive(Debug, Default)]
+pub struct HttpReqTransport;
+
+#[async_trait(?Send)]
+impl Transport for HttpReqTransport {
+    async fn get(&self, url_str: &str, headers: &[(&str, &str)]) -> Result<HttpResponse> {
+        let transport_error = |message: String| Error::Transport {
+            url: url_str.to_string(),
+            message,
+        };
+        let url = Uri::try_from(url_str).map_err(|e| transport_error(e.to_string()))?;
+        let mut writer = Vec::new();
+
+        let mut request = Request::new(&url);
+        request.method(Method::GET);
+        for (key, value) in headers {
+            request.header(key, value);
+        }
+        let res = request.send(&mut writer).map_err(|_e| {
+            log::error!("Error getting response from Github: {:?}", _e);
+            transport_error(_e.to_string())
+        })?;
+
+        Ok(HttpResponse {
+            status: u16::from(res.status_code()),
+            headers: res
+                .headers()
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            body: writer,
+        })
+    }
+}
+
+/// Serves responses registered per URL, for running the pipeline offline.
+/// Unknown URLs get a 404. Every requested URL is recorded, and clones share
+/// the record.
+#[derive(Debug, Clone, Default)]
+pub struct FixtureTransport {
+    responses: HashMap<String, HttpResponse>,
+    requests: Rc<RefCell<Vec<String>>>,
+}
+
+impl FixtureTransport {
+    pub fn new() -> FixtureTransport {
+        FixtureTransport::default()
+    }
+
+    /// Answers `url` with a 200 and `body`, or a 304 when asked with the
+    /// `ETag` of a response registered through [`FixtureTransport::with_response`].
+    pub fn with(self, url: &str, body: impl Into<Vec<u8>>) -> FixtureTransport {
+        self.with_response(
+            url,
+            HttpResponse {
+                status: 200,
+                headers: vec![],
+                body: body.into(),
+            },
+        )
+    }
+
+    pub fn with_response(mut self, url: &str, response: HttpResponse) -> FixtureTransport {
+        self.responses.insert(url.to_string(), response);
+        self
+    }
+
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.borrow().clone()
+    }
+}
+
+#[async_trait(?Send)]
+impl Transport for FixtureTransport {
+    async fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<HttpResponse> {
+        self.requests.borrow_mut().push(url.to_string());
+        let Some(response) = self.responses.get(url) else {
+            return Ok(HttpResponse {
+                status: 404,
+                headers: vec![],
+                body: br#"{"message":"Not Found"}"#.to_vec(),
+            });
+        };
+
+        // answer conditional requests like GitHub does
+        let if_none_match = headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("If-None-Match"))
+            .map(|(_, value)| *value);
+        match (response.header("ETag"), if_none_match) {
+            (Some(etag), Some(tag)) if etag == tag => Ok(HttpResponse {
+                status: 304,
+                headers: response.headers.clone(),
+                body: vec![],
+            }),
+            _ => Ok(response.clone()),
+        }
+    }
+}
+
+/// A successful response, with the headers the callers care about.
+#[derive(Debug, Clone)]
+pub struct GithubResponse {
+    pub body: Vec<u8>,
+    /// The raw `Link` header, present on paginated list endpoints.
+    pub link: Option<String>,
+}
+
+/// The GitHub Enterprise Server instances `--host` may point at, each with a
+/// token of its own. Read from `github_enterprise_hosts`, a comma separated
+/// list of `<host>=<token>`, e.g. `github.example.com=ghp_...`. Hosts are
+/// reached over https, unless an entry is written as `http://<host>=<token>`.
+#[derive(Debug, Clone, Default)]
+pub struct EnterpriseHosts {
+    /// Base URLs, e.g. `https://github.example.com`, and their tokens.
+    hosts: Vec<(String, String)>,
+}
+
+impl EnterpriseHosts {
+    pub fn from_env() -> EnterpriseHosts {
+        EnterpriseHosts::parse(&env::var("github_enterprise_hosts").unwrap_or_default())
+    }
+
+    /// Parses a list in the form of `github_enterprise_hosts`. Entries without
+    /// a token are skipped.
+    pub fn parse(list: &str) -> EnterpriseHosts {
+        let hosts = list
+            .split(',')
+            .filter_map(|entry| entry.split_once('='))
+            .map(|(host, token)| (host.trim().trim_end_matches('/'), token.trim()))
+            .filter(|(host, token)| !host.is_empty() && !token.is_empty())
+            .map(|(host, token)| {
+                let base_url = match host.contains("://") {
+                    true => host.to_lowercase(),
+                    false => format!("https://{}", host.to_lowercase()),
+                };
+                (base_url, token.to_string())
+            })
+            .collect();
+        EnterpriseHosts { hosts }
+    }
+
+    /// The token for the host at `base_url`, if it is on the list. The scheme
+    /// has to match too, so a host listed for https isn't reached over http.
+    pub fn token(&self, base_url: &str) -> Option<&str> {
+        let base_url = base_url.trim_end_matches('/');
+        self.hosts
+            .iter()
+            .find(|(host, _)| host.eq_ignore_ascii_case(base_url))
+            .map(|(_, token)| token.as_str())
+    }
+}
+
+/// Talks to one GitHub API through a [`Transport`]. Made once per command, so
+/// the rate limit state covers exactly one report.
+pub struct GithubClient {
+    transport: Box<dyn Transport>,
+    token: String,
+    api_base: String,
+    web_base: String,
+    max_retries: u32,
+    max_wait: u64,
+    rate_limited: Cell<bool>,
+    cache: Box<dyn Cache>,
+}
+
+/// A response kept for revalidation with a conditional request.
+#[derive(Serialize, Deserialize)]
+struct CachedResponse {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    link: Option<String>,
+    body: String,
+}
+
+impl GithubClient {
+    /// A client for the API at `api_base`. The web host, which serves commit
+    /// patches, is derived from it: `https://api.github.com` maps to
+    /// `https://github.com` and `https://{host}/api/v3` to `https://{host}`.
+    pub fn new(transport: Box<dyn Transport>, token: &str, api_base: &str) -> GithubClient {
+        let api_base = api_base.trim_end_matches('/');
+        let web_base = match api_base.strip_suffix("/api/v3") {
+            Some(web_base) => web_base.to_string(),
+            None => api_base.replace("://api.github.com", "://github.com"),
+        };
+        GithubClient {
+            transport,
+            token: token.to_string(),
+            api_base: api_base.to_string(),
+            web_base,
+            max_retries: 3,
+            max_wait: 60,
+            rate_limited: Cell::new(false),
+            cache: Box::new(NoCache),
+        }
+    }
+
+    /// Keeps responses in `cache` and revalidates them with `If-None-Match`
+    /// or `If-Modified-Since`. GitHub answers an unchanged resource with a
+    /// 304, which doesn't count against the rate limit. Only API responses are
+    /// kept: patches from the web host never change once fetched, and the
+    /// summaries made from them are cached already.
+    pub fn with_cache(mut self, cache: Box<dyn Cache>) -> GithubClient {
+        self.cache = cache;
+        self
+    }
+
+    /// Reads `github_token`, `github_api_url` (default `https://api.github.com`),
+    /// `github_web_url` (default derived from the API URL), `github_max_retries`
+    /// and `github_max_wait_secs`. Responses are cached as configured for
+    /// [`cache::http_from_env`].
+    pub fn from_env() -> GithubClient {
+        let token = env::var("github_token").unwrap_or("fake-token".to_string());
+        let api_base = env::var("github_api_url").unwrap_or("https://api.github.com".to_string());
+        let mut client = GithubClient::new(Box::new(HttpReqTransport), &token, &api_base)
+            .with_cache(cache::http_from_env());
+        if let Ok(web_base) = env::var("github_web_url") {
+            client.web_base = web_base.trim_end_matches('/').to_string();
+        }
+        if let Some(n) = env::var("github_max_retries")
+            .ok()
+            .and_then(|n| n.parse().ok())
+        {
+            client.max_retries = n;
+        }
+        if let Some(n) = env::var("github_max_wait_secs")
+            .ok()
+            .and_then(|n| n.parse().ok())
+        {
+            client.max_wait = n;
+        }
+        client
+    }
+
+    /// Points the client at the GitHub Enterprise Server at `base_url`, e.g.
+    /// `https://github.example.com`, with the token `hosts` has for it. The
+    /// configured host keeps its token; any host missing from `hosts` is
+    /// refused, so the bot's tokens only ever go where they belong.
+    pub fn for_host(mut self, base_url: &str, hosts: &EnterpriseHosts) -> Result<GithubClient> {
+        let base_url = base_url.trim_end_matches('/');
+        if base_url.eq_ignore_ascii_case(&self.web_base) {
+            return Ok(self);
+        }
+        let Some(token) = hosts.token(base_url) else {
+            return Err(Error::HostNotAllowed {
+                host: base_url.to_string(),
+            });
+        };
+        self.api_base = match base_url.strip_suffix("://github.com") {
+            Some(scheme) => format!("{scheme}://api.github.com"),
+            None => format!("{base_url}/api/v3"),
+        };
+        self.web_base = base_url.to_string();
+        self.token = token.to_string();
+        Ok(self)
+    }
+
+    /// The API URL for `path`, e.g. `/repos/{owner}/{repo}`.
+    pub fn api_url(&self, path: &str) -> String {
+        format!("{}{path}", self.api_base)
+    }
+
+    /// The web URL for `path`, e.g. `/{owner}/{repo}/commit/{sha}.patch`.
+    pub fn web_url(&self, path: &str) -> String {
+        format!("{}{path}", self.web_base)
+    }
+
+    /// Whether any request gave up because of GitHub's rate limits, which
+    /// means a report built from the responses is partial.
+    pub fn was_rate_limited(&self) -> bool {
+        self.rate_limited.get()
+    }
+
+    pub async fn fetch(&self, url: &str) -> Result<Vec<u8>> {
+        self.get(url).await.map(|res| res.body)
+    }
+
+    fn store(&self, cache_key: &str, res: &HttpResponse, link: &Option<String>) {
+        let etag = res.header("ETag").map(|v| v.to_string());
+        let last_modified = res.header("Last-Modified").map(|v| v.to_string());
+        if etag.is_none() && last_modified.is_none() {
+            return;
+        }
+        let Ok(body) = std::str::from_utf8(&res.body) else {
+            return;
+        };
+        let entry = CachedResponse {
+            etag,
+            last_modified,
+            link: link.clone(),
+            body: body.to_string(),
+        };
+        if let Ok(entry) = serde_json::to_string(&entry) {
+            self.cache.put(cache_key, &entry);
+        }
+    }
+
+    pub async fn get(&self, url: &str) -> Result<GithubResponse> {
+        let authorization = format!("Bearer {}", self.token);
+        let mut headers = vec![
+            ("User-Agent", "flows-network connector"),
+            ("Content-Type", "application/vnd.github.v3+json"),
+            ("Authorization", authorization.as_str()),
+        ];
+        let cache_key = format!("http/{url}");
+        let cacheable = url.starts_with(&self.api_base);
+        let cached = cacheable
+            .then(|| self.cache.get(&cache_key))
+            .flatten()
+            .and_then(|entry| serde_json::from_str::<CachedResponse>(&entry).ok());
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                headers.push(("If-None-Match", etag));
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                headers.push(("If-Modified-Since", last_modified));
+            }
+        }
+
+        let mut attempt = 0;
+        loop {
+            let res = self.transport.get(url, &headers).await?;
+            if let (304, Some(cached)) = (res.status, &cached) {
+                return Ok(GithubResponse {
+                    link: cached.link.clone(),
+                    body: cached.body.clone().into_bytes(),
+                });
+            }
+            if (200..300).contains(&res.status) {
+                let link = res.header("Link").map(|link| link.to_string());
+                if cacheable {
+                    self.store(&cache_key, &res, &link);
+                }
+                return Ok(GithubResponse {
+                    link,
+                    body: res.body,
+                });
+            }
+
+            let status = res.status;
+            let Some(wait) = rate_limit_wait(&res, attempt) else {
+                log::error!("Github http error {status} for {url}");
+                let url = url.to_string();
+                return Err(match status {
+                    401 | 403 => Error::Auth { status, url },
+                    404 => Error::NotFound { url },
+                    _ => Error::Http { status, url },
+                });
+            };
+
+            if attempt >= self.max_retries || wait > self.max_wait {
+                log::error!(
+                    "Github rate limit hit, giving up after {attempt} retries (next wait {wait}s)"
+                );
+                self.rate_limited.set(true);
+                return Err(Error::RateLimited {
+                    url: url.to_string(),
+                });
+            }
+            attempt += 1;
+            log::warn!(
+                "Github rate limit hit, retry {attempt}/{} in {wait}s",
+                self.max_retries
+            );
+            tokio::time::sleep(Duration::from_secs(wait)).await;
+        }
+    }
+
+    /// Fetches `url` and the pages after it, following `Link: rel="next"` until
+    /// `max_items` are collected. Search endpoints are additionally capped at
+    /// [`SEARCH_RESULT_LIMIT`]. Fails only if the first page does, later failures
+    /// mark the result as incomplete.
+    pub async fn paginate<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        max_items: usize,
+    ) -> Result<Paginated<T>> {
+        let is_search = url.contains("/search/");
+        let max_items = match is_search {
+            true => max_items.clamp(1, SEARCH_RESULT_LIMIT),
+            false => max_items.max(1),
+        };
+        let per_page = max_items.min(100);
+        let separator = if url.contains('?') { '&' } else { '?' };
+
+        let mut out = Paginated {
+            items: vec![],
+            total_count: None,
+            incomplete_results: false,
+        };
+        let mut next_url = Some(format!("{url}{separator}per_page={per_page}"));
+        let mut last_url = None;
+        while let Some(page_url) = next_url.take() {
+            let res = match self.get(&page_url).await {
+                Ok(res) => res,
+                Err(e) if out.items.is_empty() => return Err(e),
+                Err(e) => {
+                    log::error!("{e}, continuing with {} items", out.items.len());
+                    out.incomplete_results = true;
+                    return Ok(out);
+                }
+            };
+            match serde_json::from_slice::<PageBody<T>>(&res.body) {
+                Err(_e) => {
+                    log::error!("Error parsing page of {page_url}: {:?}", _e);
+                    if out.items.is_empty() {
+                        return Err(Error::Json {
+                            what: page_url,
+                            message: _e.to_string(),
+                        });
+                    }
+                    out.incomplete_results = true;
+                    return Ok(out);
+                }
+                Ok(PageBody::List(items)) => out.items.extend(items),
+                Ok(PageBody::Search(page)) => {
+                    out.incomplete_results |= page.incomplete_results.unwrap_or(false);
+                    if let Some(count) = page.total_count {
+                        out.total_count = Some(count as usize);
+                    }
+                    out.items.extend(page.items);
+                }
+            }
+
+            let link = res.link.unwrap_or_default();
+            last_url = link_url(&link, "last");
+            if out.items.len() < max_items {
+                next_url = link_url(&link, "next");
+            }
+        }
+        out.items.truncate(max_items);
+
+        if out.total_count.is_none() {
+            out.total_count = match last_url {
+                // every page was read
+                None => Some(out.items.len()),
+                // stopped at the cap, count the last page to know how many there are
+                Some(last_url) => {
+                    let last_page_len = self
+                        .get(&last_url)
+                        .await
+                        .ok()
+                        .and_then(|res| {
+                            serde_json::from_slice::<Vec<serde_json::Value>>(&res.body).ok()
+                        })
+                        .map(|page| page.len());
+                    match (page_number(&last_url), last_page_len) {
+                        (Some(page), Some(len)) => Some((page - 1) * per_page + len),
+                        _ => None,
+                    }
+                }
+            };
+        }
+
+        Ok(out)
+    }
+}
+
+/// Seconds to wait before retrying a rate limited request, `None` if the
+/// response is an ordinary error.
+///
+/// GitHub signals the primary limit with `x-ratelimit-remaining: 0` and the
+/// reset time in `x-ratelimit-reset`, and secondary limits with a 403/429 that
+/// may carry `retry-after`; without one the docs ask to wait at least a minute,
+/// growing exponentially with each retry.
+fn rate_limit_wait(res: &HttpResponse, attempt: u32) -> Option<u64> {
+    let status = res.status;
+    if status != 403 && status != 429 {
+        return None;
+    }
+    if let Some(secs) = res
+        .header("Retry-After")
+        .and_then(|v| v.parse::<u64>().ok())
+    {
+        return Some(secs);
+    }
+    if res.header("X-RateLimit-Remaining") == Some("0") {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let reset = res
+            .header("X-RateLimit-Reset")
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(now + 60);
+        return Some(reset.saturating_sub(now) + 1);
+    }
+    let is_secondary = status == 429
+        || String::from_utf8_lossy(&res.body)
+            .to_lowercase()
+            .contains("rate limit");
+    match is_secondary {
+        true => Some(60 * 2u64.pow(attempt.min(4))),
+        false => None,
+    }
+}
+
+/// Finds the URL with relation `rel` (`next`, `last`, ...) in a `Link` header like
+/// `<https://api.github.com/...&page=2>; rel="next", <...&page=5>; rel="last"`.
+pub fn link_url(link: &str, rel: &str) -> Option<String> {
+    link.split(',').find_map(|entry| {
+        let (url, params) = entry.split_once(';')?;
+        let is_rel = params.split(';').any(|param| {
+            param
+                .trim()
+                .strip_prefix("rel=")
+                .map(|v| v.trim_matches('"'))
+                == Some(rel)
+        });
+        match is_rel {
+            true => Some(
+                url.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string(),
+            ),
+            false => None,
+        }
+    })
+}
+
+/// The `page` query parameter of a paginated URL.
+pub fn page_number(url: &str) -> Option<usize> {
+    let (_, query) = url.split_once('?')?;
+    query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("page="))
+        .and_then(|page| page.parse().ok())
+}
+
+#[derive(Debug, Deserialize)]
+struct Page<T> {
+    pub items: Vec<T>,
+    pub incomplete_results: Option<bool>,
+    pub total_count: Option<u64>,
+}
+
+/// List endpoints return a bare array, search endpoints wrap it in a [`Page`].
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum PageBody<T> {
+    List(Vec<T>),
+    Search(Page<T>),
+}
+
+/// Items collected from a paginated endpoint.
+#[derive(Debug, Clone)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    /// All matching items, `None` when it couldn't be determined.
+    pub total_count: Option<usize>,
+    /// GitHub flagged the search as incomplete, or a page after the first failed.
+    pub incomplete_results: bool,
+}
+
+impl<T> Paginated<T> {
+    pub fn coverage(&self, noun: &'static str) -> Coverage {
+        Coverage {
+            noun,
+            considered: self.items.len(),
+            total: self.total_count,
+            incomplete_results: self.incomplete_results,
+        }
+    }
+}
+
+/// How much of the available material a report is based on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    pub noun: &'static str,
+    pub considered: usize,
+    pub total: Option<usize>,
+    pub incomplete_results: bool,
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Coverage {
+            noun,
+            considered,
+            total,
+            ..
+        } = self;
+        match total {
+            Some(total) if total > considered => {
+                write!(f, "Considered the latest {considered} of {total} {noun}.")?
+            }
+            Some(total) => write!(f, "Considered all {total} {noun}.")?,
+            None => write!(
+                f,
+                "Considered the latest {considered} {noun}, the total could not be determined."
+            )?,
+        }
+        if self.incomplete_results {
+            write!(
+                f,
+                " GitHub returned incomplete results, some {noun} may be missing."
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,16 +1,38 @@
+use cache::Cache;
+use classify::Classifier;
+use command::{usage, Command, ReportArgs};
+use config::{DebugOutput, Stage, StageConfig};
 use dotenv::dotenv;
+use error::{Error, Result};
 use flowsnet_platform_sdk::logger;
+use github::{Coverage, EnterpriseHosts, GithubClient, Paginated};
 use github_flows::octocrab::models::issues::{Comment, Issue};
-use http_req::{request::Method, request::Request, uri::Uri};
-use log;
-use openai_flows::{
-    chat::{ChatModel, ChatOptions},
-    OpenAIFlows,
-};
+use llm::{ChatMessage, ChatProvider, ChatRequest};
+use patch::Patch;
+use pulls::{analyze_pull, summarize_pulls, summarize_reviews};
+use redact::Redactor;
 use serde::{Deserialize, Serialize};
-use slack_flows::{listen_to_channel, send_message_to_channel, SlackMessage};
+use slack::{SlackFlowsSink, SlackSink};
+use slack_flows::{listen_to_channel, SlackMessage};
+use stats::{CommitFacts, IssueFacts, MergedPull, Stats};
+use std::cmp::Reverse;
 use std::env;
-use urlencoding;
+
+pub mod cache;
+pub mod classify;
+pub mod command;
+pub mod config;
+pub mod date_range;
+pub mod error;
+pub mod github;
+pub mod llm;
+pub mod patch;
+pub mod pulls;
+pub mod redact;
+pub mod roles;
+pub mod slack;
+pub mod stats;
+pub mod tokens;
 
 #[no_mangle]
 #[tokio::main(flavor = "current_thread")]
@@ -28,106 +50,546 @@ pub async fn run() {
 }
 
 async fn handler(workspace: &str, channel: &str, sm: SlackMessage) {
-    let trigger_word = env::var("trigger_word").unwrap_or("bot@get".to_string());
-
-    let parts: Vec<&str> = sm
-        .text
-        .split(&trigger_word)
-        .nth(1) // skip the part before "bot@get"
-        .unwrap_or("") // if "bot@get" is not found, use an empty string
-        .split_whitespace()
-        .collect();
+    Bot::from_env(workspace)
+        .handle(workspace, channel, &sm.text)
+        .await;
+}
 
-    let (owner, repo, user_name) = match parts.as_slice() {
-        [owner, repo, user, ..] => (owner, repo, user),
-        _ => panic!("Input should contain 'bot@get <github_owner> <github_repo> <user_name>'"),
-    };
+/// Everything a command talks to. A bot answers a single message, so that
+/// state like the rate limit notice doesn't carry over to the next one.
+pub struct Bot {
+    pub trigger_word: String,
+    pub github: GithubClient,
+    /// The servers `--host` may point at.
+    pub hosts: EnterpriseHosts,
+    pub llm: Box<dyn ChatProvider>,
+    pub slack: Box<dyn SlackSink>,
+    pub cache: Box<dyn Cache>,
+    pub debug: DebugOutput,
+}
 
-    if sm.text.contains(&trigger_word) {
-        if let Some(res) = analyze_commits(owner, repo, user_name).await {
-            send_message_to_channel("ik8", "ch_in", res.clone()).await;
-            let commits_summaries = res;
-            if let Some(issues) = get_issues(owner, repo, user_name).await {
-                let mut issues_summaries = String::new();
-                for issue in issues {
-                    if let Some(body) = analyze_issue(owner, repo, user_name, issue).await {
-                        issues_summaries.push_str(&body);
-                        issues_summaries.push_str("\n");
-                    }
+impl Bot {
+    pub fn from_env(workspace: &str) -> Bot {
+        Bot {
+            trigger_word: env::var("trigger_word").unwrap_or("bot@get".to_string()),
+            github: GithubClient::from_env(),
+            hosts: EnterpriseHosts::from_env(),
+            llm: llm::from_env(),
+            slack: Box::new(SlackFlowsSink),
+            cache: cache::from_env(),
+            debug: DebugOutput::from_env(workspace),
+        }
+    }
+
+    /// Answers `text`, a message posted in `channel`, if it is addressed to the bot.
+    pub async fn handle(self, workspace: &str, channel: &str, text: &str) {
+        let trigger_word = &self.trigger_word;
+
+        // messages without the trigger word are regular chatter in the channel
+        let Some(input) = text.split(trigger_word.as_str()).nth(1) else {
+            return;
+        };
+
+        let command = match Command::parse(input) {
+            Ok(command) => command,
+            Err(e) => {
+                let reply = format!(
+                    "Sorry, I couldn't understand that command: {e}.\n{}",
+                    usage(trigger_word)
+                );
+                self.slack.send(workspace, channel, reply).await;
+                return;
+            }
+        };
+
+        let github = match command.host() {
+            Some(host) => match self.github.for_host(host, &self.hosts) {
+                Ok(github) => github,
+                Err(e) => {
+                    let reply = format!("Sorry, I couldn't build the report: {e}.");
+                    self.slack.send(workspace, channel, reply).await;
+                    return;
                 }
-                send_message_to_channel("ik8", "ch_mid", issues_summaries.clone()).await;
+            },
+            None => self.github,
+        };
+        let llm = &*self.llm;
+        let report = match command {
+            Command::Help => Ok(usage(trigger_word)),
+            Command::Commits(args) => commits_report(&github, llm, &*self.cache, &args).await,
+            Command::Issues(args) => issues_report(&github, llm, &*self.cache, &args).await,
+            Command::Reviews(args) => reviews_report(&github, llm, &*self.cache, &args).await,
+            Command::Contrib(args) => {
+                let debug = DebugTarget {
+                    output: &self.debug,
+                    slack: &*self.slack,
+                    workspace,
+                    channel,
+                };
+                contrib_report(&github, llm, &*self.cache, &debug, &args).await
+            }
+        };
+
+        let reply = match (report, github.was_rate_limited()) {
+            (Ok(report), false) => report,
+            (Ok(report), true) => format!(
+                "{report}\n:warning: GitHub's rate limit was hit while building this report, so it is partial. Try again later for a complete one."
+            ),
+            (Err(e), _) => format!("Sorry, I couldn't build the report: {e}."),
+        };
+        self.slack.send(workspace, channel, reply).await;
+    }
+}
+
+/// Where [`contrib_report`] posts its intermediate results.
+struct DebugTarget<'a> {
+    output: &'a DebugOutput,
+    slack: &'a dyn SlackSink,
+    workspace: &'a str,
+    channel: &'a str,
+}
+
+impl DebugTarget<'_> {
+    async fn post(&self, label: &str, text: &str) {
+        self.output
+            .post(self.slack, self.workspace, self.channel, label, text)
+            .await
+    }
+}
+
+/// Makes sure the repository and the user exist, so that a typo is reported
+/// as such rather than as an empty report.
+pub async fn check_target(github: &GithubClient, args: &ReportArgs) -> Result<()> {
+    let ReportArgs {
+        owner, repo, user, ..
+    } = args;
+
+    let repo_url = github.api_url(&format!("/repos/{owner}/{repo}"));
+    match github.fetch(&repo_url).await {
+        Err(Error::NotFound { .. }) => {
+            return Err(Error::RepoNotFound {
+                owner: owner.clone(),
+                repo: repo.clone(),
+            })
+        }
+        Err(e) => return Err(e),
+        Ok(_) => {}
+    }
 
-                if let Some(report) =
-                    correlate_commits_issues(&commits_summaries, &issues_summaries).await
-                {
-                    send_message_to_channel(workspace, channel, report).await;
+    let user_url = github.api_url(&format!("/users/{user}"));
+    match github.fetch(&user_url).await {
+        Err(Error::NotFound { .. }) => Err(Error::UserNotFound { user: user.clone() }),
+        Err(e) => Err(e),
+        Ok(_) => Ok(()),
+    }
+}
+
+async fn commits_report(
+    github: &GithubClient,
+    llm: &dyn ChatProvider,
+    cache: &dyn Cache,
+    args: &ReportArgs,
+) -> Result<String> {
+    check_target(github, args).await?;
+
+    let commits = analyze_commits(github, llm, cache, args).await?;
+    let mut stats = Stats::new(args.range);
+    stats.add_commits(&commits.facts, &commits.coverage);
+    Ok(format!(
+        "{}{}\n{}",
+        stats.render(),
+        commits.coverage,
+        commits.summaries
+    ))
+}
+
+async fn issues_report(
+    github: &GithubClient,
+    llm: &dyn ChatProvider,
+    cache: &dyn Cache,
+    args: &ReportArgs,
+) -> Result<String> {
+    check_target(github, args).await?;
+
+    let issues = summarize_issues(github, llm, cache, args).await?;
+    let mut stats = Stats::new(args.range);
+    stats.add_issues(&issues.facts, &issues.coverage);
+    add_merged_pulls(github, args, &mut stats).await;
+    Ok(format!(
+        "{}{}\n*Issues*\n{}*Pull requests*\n{}",
+        stats.render(),
+        issues.coverage,
+        or_none(&issues.summaries),
+        or_none(&issues.pulls)
+    ))
+}
+
+async fn reviews_report(
+    github: &GithubClient,
+    llm: &dyn ChatProvider,
+    cache: &dyn Cache,
+    args: &ReportArgs,
+) -> Result<String> {
+    check_target(github, args).await?;
+
+    let pulls = summarize_pulls(github, llm, cache, args).await?;
+    let reviews = summarize_reviews(github, llm, cache, args).await?;
+    let mut stats = Stats::new(args.range);
+    add_merged_pulls(github, args, &mut stats).await;
+    stats.add_reviews(&reviews.facts);
+    Ok(format!(
+        "{}{} {}\n*Pull requests authored*\n{}*Reviews given*\n{}",
+        stats.render(),
+        pulls.coverage,
+        reviews.coverage,
+        or_none(&pulls.summaries),
+        or_none(&reviews.summaries)
+    ))
+}
+
+/// A report section, or a note that there is nothing in it.
+fn or_none(section: &str) -> &str {
+    match section.trim().is_empty() {
+        true => "None in this period.\n",
+        false => section,
+    }
+}
+
+async fn contrib_report(
+    github: &GithubClient,
+    llm: &dyn ChatProvider,
+    cache: &dyn Cache,
+    debug: &DebugTarget<'_>,
+    args: &ReportArgs,
+) -> Result<String> {
+    check_target(github, args).await?;
+
+    let commits = analyze_commits(github, llm, cache, args).await?;
+    let issues = summarize_issues(github, llm, cache, args).await?;
+    let reviews = summarize_reviews(github, llm, cache, args).await;
+    debug.post("commit summaries", &commits.summaries).await;
+    debug.post("issue summaries", &issues.summaries).await;
+    debug.post("pull request summaries", &issues.pulls).await;
+
+    let report =
+        correlate_commits_issues(llm, &commits.summaries, &issues.summaries, &issues.pulls).await?;
+    let mut stats = Stats::new(args.range);
+    stats.add_commits(&commits.facts, &commits.coverage);
+    stats.add_issues(&issues.facts, &issues.coverage);
+    add_merged_pulls(github, args, &mut stats).await;
+
+    // reviews are a section of their own, and the report stands without them
+    let reviews = match reviews {
+        Ok(reviews) if !reviews.summaries.trim().is_empty() => {
+            stats.add_reviews(&reviews.facts);
+            debug.post("review summaries", &reviews.summaries).await;
+            let budget = StageConfig::from_env(Stage::Correlation).summary_tokens as usize * 2;
+            match condense_summaries(llm, "code reviews", &reviews.summaries, budget).await {
+                Ok(reviews) => format!("\n*Code review*\n{}", reviews.trim_end()),
+                Err(e) => {
+                    log::error!("Error condensing review summaries: {e}");
+                    String::new()
                 }
             }
         }
+        Ok(reviews) => {
+            stats.add_reviews(&reviews.facts);
+            String::new()
+        }
+        Err(e) => {
+            log::error!("Error summarizing reviews: {e}");
+            String::new()
+        }
+    };
+    Ok(format!(
+        "{}{} {}\n{report}{reviews}",
+        stats.render(),
+        commits.coverage,
+        issues.coverage
+    ))
+}
+
+/// The issue and pull request summaries of a report, what the user did on
+/// those summarized and how many of the user's issues they cover.
+#[derive(Debug, Clone, Default)]
+pub struct IssuesSummary {
+    pub summaries: String,
+    pub pulls: String,
+    pub facts: Vec<IssueFacts>,
+    pub coverage: Coverage,
+}
+
+/// Summarizes every issue and pull request involving the user, each kind
+/// with its own prompt. Those that fail are skipped, unless all of them do,
+/// in which case the first error is returned.
+pub async fn summarize_issues(
+    github: &GithubClient,
+    llm: &dyn ChatProvider,
+    cache: &dyn Cache,
+    args: &ReportArgs,
+) -> Result<IssuesSummary> {
+    let issues = get_issues(github, args).await?;
+    let coverage = issues.coverage("issues and pull requests");
+    let (pulls, issues): (Vec<Issue>, Vec<Issue>) = issues
+        .items
+        .into_iter()
+        .partition(|issue| issue.pull_request.is_some());
+
+    let mut issue_results = Vec::new();
+    for issue in issues {
+        issue_results.push(analyze_issue(github, llm, cache, args, issue).await);
+    }
+    let mut pull_results = Vec::new();
+    for pull in pulls {
+        pull_results.push(analyze_pull(github, llm, cache, args, pull).await);
     }
+
+    let (summaries, mut facts) = split_analyses(by_role(issue_results));
+    let (pulls, pull_facts) = split_analyses(by_role(pull_results));
+    facts.extend(pull_facts);
+    // an error stands for the whole result only if nothing else came back
+    let (summaries, pulls) = match (join_summaries(summaries), join_summaries(pulls)) {
+        (Err(e), Err(_)) => return Err(e),
+        (Err(e), Ok(pulls)) if pulls.is_empty() => return Err(e),
+        (Ok(summaries), Err(e)) if summaries.is_empty() => return Err(e),
+        (summaries, pulls) => (summaries.unwrap_or_default(), pulls.unwrap_or_default()),
+    };
+    Ok(IssuesSummary {
+        summaries,
+        pulls,
+        facts,
+        coverage,
+    })
+}
+
+/// Drops what `--role` filtered out and puts the user's own work first,
+/// newest first among equal roles.
+fn by_role(
+    results: Vec<Result<Option<Analysis<IssueFacts>>>>,
+) -> Vec<Result<Analysis<IssueFacts>>> {
+    let mut results: Vec<Result<Analysis<IssueFacts>>> =
+        results.into_iter().filter_map(Result::transpose).collect();
+    results.sort_by_key(|result| {
+        Reverse(
+            result
+                .as_ref()
+                .map_or(0, |analysis| roles::weight(&analysis.facts.roles)),
+        )
+    });
+    results
 }
-#[derive(Debug, Deserialize)]
-struct Page<T> {
-    pub items: Vec<T>,
-    // pub incomplete_results: Option<bool>,
-    pub total_count: Option<u64>,
-    // pub next: Option<String>,
-    // pub prev: Option<String>,
-    // pub first: Option<String>,
-    // pub last: Option<String>,
-}
-pub async fn get_issues(owner: &str, repo: &str, user: &str) -> Option<Vec<Issue>> {
-    let github_token = env::var("github_token").unwrap_or("fake-token".to_string());
-    let query = format!("repo:{owner}/{repo} involves:{user}");
+
+/// Lists issues and pull requests involving the user, newest first, up to
+/// `max_issues` (env, default 90).
+pub async fn get_issues(github: &GithubClient, args: &ReportArgs) -> Result<Paginated<Issue>> {
+    let ReportArgs {
+        owner, repo, user, ..
+    } = args;
+    let query = format!(
+        "repo:{owner}/{repo} involves:{user}{}",
+        args.range.search_qualifiers()
+    );
     let encoded_query = urlencoding::encode(&query);
 
-    let mut out: Vec<Issue> = vec![];
-    let mut total_pages = None;
-    for page in 1..=3 {
-        if page > total_pages.unwrap_or(3) {
-            break;
-        }
+    let url_str = github.api_url(&format!(
+        "/search/issues?q={encoded_query}&sort=created&order=desc"
+    ));
+    github.paginate(&url_str, env_limit("max_issues", 90)).await
+}
 
-        let url_str = format!(
-            "https://api.github.com/search/issues?q={encoded_query}&sort=created&order=desc&page={page}"
-        );
-
-        match github_http_fetch(&github_token, &url_str).await {
-            Some(res) => match serde_json::from_slice::<Page<Issue>>(&res) {
-                Err(_e) => log::error!("Error parsing Page<Issue>: {:?}", _e),
-
-                Ok(issue_page) => {
-                    if total_pages.is_none() {
-                        if let Some(count) = issue_page.total_count {
-                            total_pages = Some((count / 30) as usize + 1);
-                        }
-                    }
-                    for issue in issue_page.items {
-                        out.push(issue);
-                    }
-                }
-            },
+/// Lists the user's pull requests merged within the report's range, up to
+/// `max_issues` (env, default 90).
+pub async fn list_merged_pulls(
+    github: &GithubClient,
+    args: &ReportArgs,
+) -> Result<Paginated<MergedPull>> {
+    let ReportArgs {
+        owner, repo, user, ..
+    } = args;
+    let query = format!(
+        "repo:{owner}/{repo} is:pr is:merged author:{user}{}",
+        args.range.search_qualifier("merged")
+    );
+    let encoded_query = urlencoding::encode(&query);
 
-            None => {}
+    let url_str = github.api_url(&format!(
+        "/search/issues?q={encoded_query}&sort=created&order=desc"
+    ));
+    github.paginate(&url_str, env_limit("max_issues", 90)).await
+}
+
+/// Adds the user's merged pull requests to `stats`. Without them the report
+/// is still worth having, so a failure is only logged.
+async fn add_merged_pulls(github: &GithubClient, args: &ReportArgs, stats: &mut Stats) {
+    match list_merged_pulls(github, args).await {
+        Ok(pulls) => stats.add_merged_pulls(&pulls.items, &pulls.coverage("merged pull requests")),
+        Err(e) => log::error!("Error listing merged pull requests: {e}"),
+    }
+}
+
+/// What summarizing an issue or pull request starts from: its comments,
+/// what the user did on it and the summary, if it is cached.
+struct Discussion {
+    html_url: String,
+    cache_key: String,
+    cached_summary: Option<String>,
+    comments: Vec<Comment>,
+    facts: IssueFacts,
+    /// Whether nothing is missing, so a summary made from it can be kept.
+    cacheable: bool,
+}
+
+impl Discussion {
+    /// The analysis with `summary`, which is cached if it is new, followed by
+    /// the user's roles.
+    fn analysis(self, cache: &dyn Cache, summary: Result<String>) -> Analysis<IssueFacts> {
+        let summary = summary.map(|summary| {
+            if self.cacheable && self.cached_summary.is_none() {
+                cache.put(&self.cache_key, &summary);
+            }
+            let tag = roles::tag(&self.facts.roles);
+            format!("{} {}{tag}", self.html_url, summary.trim_end())
+        });
+        Analysis {
+            summary,
+            facts: self.facts,
         }
     }
+}
 
-    Some(out)
+/// Fetches the comments and timeline of an issue or pull request and draws
+/// the user's facts and roles from them. Returns `None` if the user has none
+/// of the roles asked for by `--role`. Nothing is fetched while both the
+/// summary and the facts are cached.
+async fn gather_discussion(
+    github: &GithubClient,
+    cache: &dyn Cache,
+    args: &ReportArgs,
+    issue: &Issue,
+    stage: &StageConfig,
+    redactor: &Redactor,
+) -> Option<Discussion> {
+    let ReportArgs {
+        owner, repo, user, ..
+    } = args;
+    let number = issue.number;
+    let (kind, noun) = match issue.pull_request.is_some() {
+        true => ("pull", "pull request"),
+        false => ("issue", "issue"),
+    };
+    let html_url = issue.html_url.to_string();
+    let updated_at = issue.updated_at.timestamp();
+
+    let cache_key = format!(
+        "{kind}/{html_url}/{updated_at}/{user}/{}",
+        summary_tag(stage, &[redactor.cache_tag()])
+    );
+    let facts_key = format!("facts/{html_url}/{updated_at}/{user}");
+    let mut discussion = Discussion {
+        html_url,
+        cached_summary: cache.get(&cache_key),
+        cache_key,
+        comments: Vec::new(),
+        facts: IssueFacts::default(),
+        cacheable: true,
+    };
+    let cached_facts = cache
+        .get(&facts_key)
+        .and_then(|facts| serde_json::from_str::<IssueFacts>(&facts).ok());
+    if let (Some(_), Some(facts)) = (&discussion.cached_summary, cached_facts) {
+        discussion.facts = facts;
+        return roles::selected(&discussion.facts.roles, &args.roles).then_some(discussion);
+    }
+
+    let url_str = github.api_url(&format!("/repos/{owner}/{repo}/issues/{number}/comments"));
+    // a summary or facts made without the comments are not worth keeping
+    discussion.comments = match github.paginate::<Comment>(&url_str, usize::MAX).await {
+        Ok(comments) => comments.items,
+        Err(e) => {
+            log::error!("Error fetching comments of {noun} #{number}: {e}");
+            discussion.cacheable = false;
+            Vec::new()
+        }
+    };
+
+    // search results don't say who was mentioned or assigned, the timeline
+    // does, nor who closed it, the issue itself does
+    let events = match roles::list_timeline(github, owner, repo, number).await {
+        Ok(events) => events,
+        Err(e) => {
+            log::error!("Error fetching the timeline of {noun} #{number}: {e}");
+            discussion.cacheable = false;
+            Vec::new()
+        }
+    };
+    let closer = match issue.closed_at {
+        Some(_) => match roles::fetch_closer(github, owner, repo, number).await {
+            Ok(closer) => closer,
+            Err(e) => {
+                log::error!("Error fetching {noun} #{number}: {e}");
+                discussion.cacheable = false;
+                None
+            }
+        },
+        None => None,
+    };
+
+    let comments = &discussion.comments;
+    discussion.facts = IssueFacts {
+        pull_request: issue.pull_request.is_some(),
+        opened: issue
+            .user
+            .login
+            .eq_ignore_ascii_case(user)
+            .then_some(issue.created_at),
+        comments: comments
+            .iter()
+            .filter(|comment| comment.user.login.eq_ignore_ascii_case(user))
+            .map(|comment| comment.created_at)
+            .collect(),
+        closed: roles::closed_by(user, issue, closer.as_deref()),
+        roles: roles::roles_of(user, issue, comments, &events, closer.as_deref()),
+    };
+    if discussion.cacheable {
+        if let Ok(json) = serde_json::to_string(&discussion.facts) {
+            cache.put(&facts_key, &json);
+        }
+    }
+    discussion.comments = latest_comments(std::mem::take(&mut discussion.comments));
+    roles::selected(&discussion.facts.roles, &args.roles).then_some(discussion)
 }
 
-pub async fn analyze_issue(owner: &str, repo: &str, user: &str, issue: Issue) -> Option<String> {
-    let github_token = env::var("github_token").unwrap_or("fake-token".to_string());
+/// Summarizes one issue with its comments, followed by the user's roles in
+/// it. Returns `None` if the user has none of the roles asked for by
+/// `--role`; a failed summary still comes with the facts. The comments and
+/// timeline aren't fetched again while both the summary and the facts are
+/// cached.
+pub async fn analyze_issue(
+    github: &GithubClient,
+    llm: &dyn ChatProvider,
+    cache: &dyn Cache,
+    args: &ReportArgs,
+    issue: Issue,
+) -> Result<Option<Analysis<IssueFacts>>> {
+    let user = args.user.as_str();
+    let stage = StageConfig::from_env(Stage::Issue);
+    let redactor = Redactor::from_env();
+    let Some(mut discussion) =
+        gather_discussion(github, cache, args, &issue, &stage, &redactor).await
+    else {
+        return Ok(None);
+    };
+    if let Some(summary) = discussion.cached_summary.clone() {
+        return Ok(Some(discussion.analysis(cache, Ok(summary))));
+    }
 
-    let issue_creator_name = issue.user.login;
+    let issue_creator_name = issue.user.login.clone();
     let issue_number = issue.number;
-    let issue_title = issue.title;
-    let issue_body = match issue.body {
-        Some(body) => squeeze_fit_comment_texts(&body, "```", 500, 0.6),
+    let issue_title = issue.title.clone();
+    let issue_body = match &issue.body {
+        Some(body) => squeeze_fit_comment_texts(&redactor.text(body), "```", 600, 0.6),
         None => "".to_string(),
     };
-    let issue_date = issue.created_at.date_naive().to_string();
-    let html_url = issue.html_url.to_string();
-
     let labels = issue
         .labels
         .into_iter()
@@ -137,87 +599,55 @@ pub async fn analyze_issue(owner: &str, repo: &str, user: &str, issue: Issue) ->
 
     let mut all_text_from_issue = format!("User '{issue_creator_name}', has submitted an issue titled '{issue_title}', labeled as '{labels}', with the following post: '{issue_body}'.");
 
-    let url_str = format!(
-        "https://api.github.com/repos/{owner}/{repo}/issues/{issue_number}/comments?per_page=100",
-    );
-
-    match github_http_fetch(&github_token, &url_str).await {
-        Some(res) => match serde_json::from_slice::<Vec<Comment>>(&res) {
-            Err(_e) => log::error!("Error parsing Vec<Comment>: {:?}", _e),
-            Ok(comments_obj) => {
-                for comment in comments_obj {
-                    let comment_body = match comment.body {
-                        Some(body) => squeeze_fit_comment_texts(&body, "```", 500, 0.6),
-                        None => "".to_string(),
-                    };
-                    let commenter = comment.user.login;
-                    let commenter_input = format!("{commenter} commented: {comment_body}");
-                    all_text_from_issue.push_str(&commenter_input);
-
-                    if all_text_from_issue.len() > 45_000 {
-                        break;
-                    }
-                }
-            }
-        },
-        None => {}
-    };
+    let mut issue_tokens = tokens::count(&all_text_from_issue);
+    for comment in std::mem::take(&mut discussion.comments) {
+        let comment_body = match comment.body {
+            Some(body) => squeeze_fit_comment_texts(&redactor.text(&body), "```", 600, 0.6),
+            None => "".to_string(),
+        };
+        let commenter = comment.user.login;
+        let commenter_input = format!("{commenter} commented: {comment_body}");
+        let remaining = stage.data_budget().saturating_sub(issue_tokens);
+        let input_tokens = tokens::count(&commenter_input);
+        if input_tokens > remaining {
+            all_text_from_issue.push_str(tokens::truncate(&commenter_input, remaining));
+            break;
+        }
+        all_text_from_issue.push_str(&commenter_input);
+        issue_tokens += input_tokens;
+    }
 
     let sys_prompt_1 = &format!("Given the information that user '{issue_creator_name}' opened an issue titled '{issue_title}', labelled as '{labels}', your task is to analyze the content of the issue posts. Extract key details including the main problem or question raised, the environment in which the issue occurred, any steps taken by the user to address the problem, relevant discussions, and any identified solutions or pending tasks.");
-    let usr_prompt_1 = &format!("Based on the GitHub issue posts: {all_text_from_issue}, please list the following key details: The main problem or question raised in the issue. The environment or conditions in which the issue occurred (e.g., hardware, OS). Any steps or actions taken by the user '{user}' or others to address the issue. Key discussions or points of view shared by participants in the issue thread. Any solutions identified, or pending tasks if the issue hasn't been resolved. The role and contribution of the user '{user}' in the issue.");
-    let usr_prompt_2 = &format!("Provide a brief summary highlighting the core problem and emphasize the overarching contribution made by '{user}' to the resolution of this issue, ensuring your response stays under 128 tokens.");
+    let usr_prompt_1 = &format!("Based on the GitHub issue posts: {all_text_from_issue}, please list the following key details: The main problem or question raised in the issue. The environment or conditions in which the issue occurred (e.g., hardware, OS). Any steps or actions taken by the user '{user}' or others to address the issue. Key discussions or points of view shared by participants in the issue thread. Any solutions identified, or pending tasks if the issue hasn't been resolved. The role and contribution of the user '{user}' in the issue{}.", match discussion.facts.roles.is_empty() {
+        true => String::new(),
+        false => format!(", who is involved as {}", roles::names(&discussion.facts.roles)),
+    });
+    let usr_prompt_2 = &format!("Provide a brief summary highlighting the core problem and emphasize the overarching contribution made by '{user}' to the resolution of this issue, ensuring your response stays under {} tokens.", stage.summary_tokens);
 
-    match chain_of_chat(
+    let issue_summary = chain_of_chat(
+        llm,
+        &stage,
         sys_prompt_1,
         usr_prompt_1,
         &format!("issue_{issue_number}"),
-        256,
         usr_prompt_2,
-        128,
-        &format!("Error generatng issue summary #{issue_number}"),
+        &format!("issue summary #{issue_number}"),
     )
-    .await
-    {
-        Some(issue_summary) => {
-            let mut out = html_url.to_string();
-            out.push(' ');
-            out.push_str(&issue_summary);
-            return Some(out);
-        }
-        None => {}
-    }
-
-    None
-}
-
-pub fn squeeze_fit_commits_issues(commits: &str, issues: &str, split: f32) -> (String, String) {
-    let mut commits_vec = commits.split_whitespace().collect::<Vec<&str>>();
-    let commits_len = commits_vec.len();
-    let mut issues_vec = issues.split_whitespace().collect::<Vec<&str>>();
-    let issues_len = issues_vec.len();
-
-    if commits_len + issues_len > 44_000 {
-        let commits_to_take = (44_000 as f32 * split) as usize;
-        match commits_len > commits_to_take {
-            true => commits_vec.truncate(commits_to_take),
-            false => {
-                let issues_to_take = 44_000 - commits_len;
-                issues_vec.truncate(issues_to_take);
-            }
-        }
-    }
-    (commits_vec.join(" "), issues_vec.join(" "))
+    .await;
+    Ok(Some(discussion.analysis(cache, issue_summary)))
 }
 
+/// Drops the quoted blocks (e.g. code fenced by ```) of a post, and cuts out
+/// its middle if the rest is longer than `max_tokens`, keeping `split` of the
+/// budget for the beginning.
 pub fn squeeze_fit_comment_texts(
     inp_str: &str,
     quote_mark: &str,
-    max_len: u16,
+    max_tokens: usize,
     split: f32,
 ) -> String {
     let mut body = String::new();
     let mut inside_quote = false;
-    let max_len = max_len as usize;
 
     for line in inp_str.lines() {
         if line.contains(quote_mark) {
@@ -231,74 +661,240 @@ pub fn squeeze_fit_comment_texts(
         }
     }
 
-    let body_len = body.split_whitespace().count();
-    let n_take_from_beginning = (max_len as f32 * split) as usize;
-    let n_keep_till_end = max_len - n_take_from_beginning;
-    match body_len > max_len {
-        false => body,
-        true => {
-            let mut body_text_vec = body.split_whitespace().collect::<Vec<&str>>();
-            let drain_to = std::cmp::min(body_len, max_len);
-            body_text_vec.drain(n_take_from_beginning..drain_to - n_keep_till_end);
-            body_text_vec.join(" ")
+    tokens::squeeze(&body, max_tokens, split)
+}
+
+/// What analyzing one commit, issue or pull request found: its facts and its
+/// summary, which can fail on its own. The facts count in the stats either
+/// way.
+#[derive(Debug)]
+pub struct Analysis<F> {
+    pub summary: Result<String>,
+    pub facts: F,
+}
+
+/// Splits analyses into their summaries, or the errors in their place, and
+/// the facts of those that got as far as gathering them.
+fn split_analyses<F>(results: Vec<Result<Analysis<F>>>) -> (Vec<Result<String>>, Vec<F>) {
+    let mut facts = Vec::new();
+    let summaries = results
+        .into_iter()
+        .map(|result| {
+            result.and_then(|analysis| {
+                facts.push(analysis.facts);
+                analysis.summary
+            })
+        })
+        .collect();
+    (summaries, facts)
+}
+
+/// The commit summaries of a report, the facts of the commits summarized and
+/// how much of the user's history they cover.
+#[derive(Debug, Clone, Default)]
+pub struct CommitsSummary {
+    pub summaries: String,
+    pub facts: Vec<CommitFacts>,
+    pub coverage: Coverage,
+}
+
+/// Lists the user's commits, newest first, up to `max_commits` (env, default 100).
+pub async fn list_commits(
+    github: &GithubClient,
+    args: &ReportArgs,
+) -> Result<Paginated<GithubCommit>> {
+    let ReportArgs {
+        owner, repo, user, ..
+    } = args;
+    let url = github.api_url(&format!(
+        "/repos/{owner}/{repo}/commits?author={user}{}",
+        args.range.commits_params()
+    ));
+    github.paginate(&url, env_limit("max_commits", 100)).await
+}
+
+/// Names everything that shapes a summary besides its material, for cache
+/// keys: the stage's model settings and a hash of the other `settings`, such
+/// as the redaction rules.
+fn summary_tag(stage: &StageConfig, settings: &[String]) -> String {
+    format!(
+        "{}-{:016x}",
+        stage.cache_tag(),
+        cache::fnv1a(&settings.join("\n"))
+    )
+}
+
+/// The last `max_comments` (env, default 100) of `items`, which GitHub lists
+/// oldest first. Comments and reviews are listed in full for the roles and
+/// stats, and only the latest go into a prompt.
+fn latest_comments<T>(mut items: Vec<T>) -> Vec<T> {
+    let max_comments = env_limit("max_comments", 100);
+    items.split_off(items.len().saturating_sub(max_comments))
+}
+
+/// Reads a positive count from the environment.
+fn env_limit(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|n| n.parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(default)
+}
+
+/// Summarizes each of the user's commits. Commits that fail are skipped,
+/// unless all of them do, in which case the first error is returned.
+pub async fn analyze_commits(
+    github: &GithubClient,
+    llm: &dyn ChatProvider,
+    cache: &dyn Cache,
+    args: &ReportArgs,
+) -> Result<CommitsSummary> {
+    let settings = CommitSettings::from_env(args);
+    let commits = list_commits(github, args).await?;
+    let coverage = commits.coverage("commits");
+
+    let mut results = Vec::new();
+    for commit in commits.items {
+        results.push(analyze_commit(github, llm, cache, &settings, args, commit.sha).await);
+    }
+
+    let (summaries, facts) = split_analyses(results);
+    Ok(CommitsSummary {
+        summaries: join_summaries(summaries)?,
+        facts,
+        coverage,
+    })
+}
+
+/// What shapes a commit summary, besides the commit itself.
+pub struct CommitSettings {
+    pub stage: StageConfig,
+    pub classifier: Classifier,
+    pub redactor: Redactor,
+}
+
+impl CommitSettings {
+    /// The settings for the commits of `args`' repository.
+    pub fn from_env(args: &ReportArgs) -> CommitSettings {
+        CommitSettings {
+            stage: StageConfig::from_env(Stage::Commit),
+            classifier: Classifier::from_env(&args.owner, &args.repo),
+            redactor: Redactor::from_env(),
         }
     }
 }
 
-pub async fn analyze_commits(owner: &str, repo: &str, user_name: &str) -> Option<String> {
-    let github_token = env::var("github_token").unwrap_or("fake-token".to_string());
-    let user_commits_repo_str =
-        format!("https://api.github.com/repos/{owner}/{repo}/commits?author={user_name}");
-    let mut commits_summaries = String::new();
-
-    match github_http_fetch(&github_token, &user_commits_repo_str).await {
-        None => log::error!("Error fetching Page of commits"),
-        Some(res) => match serde_json::from_slice::<Vec<GithubCommit>>(&res) {
-            Err(_e) => log::error!("Error parsing commits object: {:?}", _e),
-            Ok(commits_obj) => {
-                for sha in commits_obj.into_iter().map(|commit| commit.sha) {
-                    let commit_patch_str =
-                        format!("https://github.com/{owner}/{repo}/commit/{sha}.patch");
-                    match github_http_fetch(&github_token, &commit_patch_str).await {
-                        Some(res) => {
-                            let text = String::from_utf8_lossy(&res).to_string();
-
-                            let sys_prompt_1 = &format!("You are provided with a commit patch by the user {user_name} on the {repo} project. Your task is to parse this data, focusing on the following sections: the Date Line, Subject Line, Diff Files, Diff Changes, Sign-off Line, and the File Changes Summary. Extract key elements such as the date of the commit (in 'yyyy/mm/dd' format), a summary of changes, and the types of files affected, prioritizing code files, scripts, then documentation. Be particularly careful to distinguish between changes made to core code files and modifications made to documentation files, even if they contain technical content. Compile a list of the extracted key elements.");
-
-                            let usr_prompt_1 = &format!("Based on the provided commit patch: {text}, extract and present the following key elements: the date of the commit (formatted as 'yyyy/mm/dd'), a high-level summary of the changes made, and the types of files affected. Prioritize data on changes to code files first, then scripts, and lastly documentation. Pay attention to the file types and ensure the distinction between documentation changes and core code changes, even when the documentation contains highly technical language. Please compile your findings into a list, with each key element represented as a separate item.");
-
-                            let usr_prompt_2 = &format!("Using the key elements you extracted from the commit patch, provide a summary of the user's contributions to the project. Include the date of the commit, the types of files affected, and the overall changes made. When describing the affected files, make sure to differentiate between changes to core code files, scripts, and documentation files. Present your summary in this format: 'On (date in 'yyyy/mm/dd' format), (summary of changes). (overall impact of changes).' Please ensure your answer stayed below 128 tokens.");
-
-                            let sha_serial = sha.chars().take(5).collect::<String>();
-                            match chain_of_chat(
-                                sys_prompt_1,
-                                usr_prompt_1,
-                                &format!("commit-{sha_serial}"),
-                                256,
-                                usr_prompt_2,
-                                128,
-                                &format!("analyze_commits-{sha_serial}"),
-                            )
-                            .await
-                            {
-                                Some(res) => {
-                                    commits_summaries.push_str(&res);
-                                    commits_summaries.push('\n');
-                                    if commits_summaries.len() > 45_000 {
-                                        break;
-                                    }
-                                }
-                                None => continue,
-                            }
-                        }
-                        None => continue,
-                    };
-                }
-            }
-        },
+/// Fetches the patch of one commit and summarizes it. Fails only if the
+/// patch can't be had; a failed summary still comes with the facts. The
+/// patch isn't fetched again while both the summary and the facts are
+/// cached.
+pub async fn analyze_commit(
+    github: &GithubClient,
+    llm: &dyn ChatProvider,
+    cache: &dyn Cache,
+    settings: &CommitSettings,
+    args: &ReportArgs,
+    sha: String,
+) -> Result<Analysis<CommitFacts>> {
+    let CommitSettings {
+        stage,
+        classifier,
+        redactor,
+    } = settings;
+    let ReportArgs {
+        owner,
+        repo,
+        user: user_name,
+        ..
+    } = args;
+    let commit_patch_str = github.web_url(&format!("/{owner}/{repo}/commit/{sha}.patch"));
+    let cache_key = format!(
+        "commit/{commit_patch_str}/{user_name}/{}",
+        summary_tag(stage, &[redactor.cache_tag(), classifier.cache_tag()])
+    );
+    // the kinds of the files are part of the facts
+    let facts_key = format!(
+        "facts/{commit_patch_str}/{:016x}",
+        cache::fnv1a(&classifier.cache_tag())
+    );
+    let cached_summary = cache.get(&cache_key);
+    let cached_facts = cache
+        .get(&facts_key)
+        .and_then(|facts| serde_json::from_str::<CommitFacts>(&facts).ok());
+    if let (Some(summary), Some(facts)) = (&cached_summary, cached_facts) {
+        return Ok(Analysis {
+            summary: Ok(summary.clone()),
+            facts,
+        });
     }
+    let res = github.fetch(&commit_patch_str).await?;
+    let mut patch = Patch::parse(&String::from_utf8_lossy(&res));
+    patch.classify(classifier);
+    let facts = CommitFacts::from(&patch);
+    if let Ok(json) = serde_json::to_string(&facts) {
+        cache.put(&facts_key, &json);
+    }
+    if let Some(summary) = cached_summary {
+        return Ok(Analysis {
+            summary: Ok(summary),
+            facts,
+        });
+    }
+    let patch = redactor.parsed(patch);
+    let text = patch.digest(stage.data_budget());
+
+    let sys_prompt_1 = &format!("You are provided with a digest of a commit by the user {user_name} on the {repo} project. Its first lines state the facts of the commit: the date (in 'yyyy/mm/dd' format), the subject and message, and every file changed with its kind (code, test, script, docs, config, generated or other), its status and the lines added and removed; the diffs of the files follow, shortened where long. Take the facts and the kinds as given. Your task is to work out from the message and the diffs what the changes do, prioritizing code files, then tests and scripts, then documentation and configuration. Generated files, such as lockfiles, only reflect other changes and need no description of their own. Compile a list of the key elements.");
+
+    let usr_prompt_1 = &format!("Based on the provided commit digest: {text}, extract and present the following key elements: the date of the commit, a high-level summary of the changes made, and the kinds of files affected as tagged in the digest. Prioritize changes to code files first, then tests and scripts, and lastly documentation and configuration. Please compile your findings into a list, with each key element represented as a separate item.");
 
-    Some(commits_summaries)
+    let usr_prompt_2 = &format!("Using the key elements you extracted from the commit patch, provide a summary of the user's contributions to the project. Include the date of the commit, the kinds of files affected, and the overall changes made, keeping to the kinds the digest gives. Present your summary in this format: 'On (date in 'yyyy/mm/dd' format), (summary of changes). (overall impact of changes).' Please ensure your answer stayed below {} tokens.", stage.summary_tokens);
+
+    let sha_serial = sha.chars().take(5).collect::<String>();
+    let summary = chain_of_chat(
+        llm,
+        stage,
+        sys_prompt_1,
+        usr_prompt_1,
+        &format!("commit-{sha_serial}"),
+        usr_prompt_2,
+        &format!("commit summary {sha_serial}"),
+    )
+    .await
+    .map(|summary| {
+        // the kinds are stated by the bot, whatever the model made of them
+        let kinds = patch.kinds_summary();
+        let summary = match kinds.is_empty() {
+            true => summary,
+            false => format!("{} [{kinds}]", summary.trim_end()),
+        };
+        cache.put(&cache_key, &summary);
+        summary
+    });
+    Ok(Analysis { summary, facts })
+}
+
+/// Joins the summaries that succeeded, one per line, in order. Failures are
+/// logged and skipped, unless nothing succeeded, in which case the first
+/// error is returned.
+fn join_summaries(results: Vec<Result<String>>) -> Result<String> {
+    let mut summaries = String::new();
+    let mut first_error = None;
+    for result in results {
+        match result {
+            Ok(summary) => {
+                summaries.push_str(&summary);
+                summaries.push('\n');
+            }
+            Err(e) => {
+                log::error!("{e}");
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    match (summaries.is_empty(), first_error) {
+        (true, Some(e)) => Err(e),
+        _ => Ok(summaries),
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -307,120 +903,205 @@ struct User {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct GithubCommit {
+pub struct GithubCommit {
     sha: String,
     html_url: String,
-    author: User,
-    committer: User,
+    // null when the commit email isn't linked to a GitHub account
+    author: Option<User>,
+    committer: Option<User>,
 }
 
+/// Writes the contribution report from the commit, issue and pull request
+/// summaries, each given to the model as a section of its own.
 pub async fn correlate_commits_issues(
+    llm: &dyn ChatProvider,
     _commits_summary: &str,
     _issues_summary: &str,
-) -> Option<String> {
-    let (commits_summary, issues_summary) =
-        squeeze_fit_commits_issues(_commits_summary, _issues_summary, 0.6);
+    _pulls_summary: &str,
+) -> Result<String> {
+    let stage = StageConfig::from_env(Stage::Correlation);
+    let budget = stage.data_budget();
+
+    // long histories are condensed rather than cut, so the whole range is covered
+    let commits_tokens = tokens::count(_commits_summary);
+    let issues_tokens = tokens::count(_issues_summary);
+    let pulls_tokens = tokens::count(_pulls_summary);
+    let discussion_tokens = issues_tokens + pulls_tokens;
+    let (commits_budget, discussion_budget) = match commits_tokens + discussion_tokens > budget {
+        false => (budget, budget),
+        true => {
+            // commits get 60% unless either side needs less
+            let commits_budget =
+                commits_tokens.min((budget * 6 / 10).max(budget.saturating_sub(discussion_tokens)));
+            (commits_budget, budget - commits_budget)
+        }
+    };
+    // issues and pull requests share the rest by size
+    let (issues_budget, pulls_budget) = match discussion_tokens > discussion_budget {
+        false => (discussion_budget, discussion_budget),
+        true => {
+            let issues_budget = discussion_budget * issues_tokens / discussion_tokens;
+            (issues_budget, discussion_budget - issues_budget)
+        }
+    };
+    let commits_summary =
+        condense_summaries(llm, "commits", _commits_summary, commits_budget).await?;
+    let issues_summary = condense_summaries(llm, "issues", _issues_summary, issues_budget).await?;
+    let pulls_summary =
+        condense_summaries(llm, "pull requests", _pulls_summary, pulls_budget).await?;
 
     // let sys_prompt_1 = &format!("Your task is to examine and correlate both commit logs and issue records for a specific user within a GitHub repository. Despite potential limitations in the data, such as insufficient information or difficulties in finding correlations, focus on identifying the user's top 1-3 significant contributions to the project. Consider all aspects of their contributions, from the codebase to project documentation, and describe their evolution over time. Assess the overall impact of these contributions to the project's development. Create a unique, detailed summary that highlights the scope and significance of the user's contributions, avoiding verbatim repetition from the source data. If correlations between commit logs and issue records are limited, prioritize identifying the user's top contributions. Present your summary in a clear, bullet-point format.");
-    let sys_prompt_1 = &format!("Your task is to identify the 1-3 most impactful contributions by a specific user, based on the given commit logs and issue records. Pay close attention to any sequential relationships between issues and commits, and consider how they reflect the user's growth and evolution within the project. Use this data to evaluate the user's overall influence on the project's development. Provide a concise summary in bullet-point format.");
+    let sys_prompt_1 = &("Your task is to identify the 1-3 most impactful contributions by a specific user, based on the given commit logs, issue records and pull request records. Pay close attention to any sequential relationships between issues, pull requests and commits, and consider how they reflect the user's growth and evolution within the project. Use this data to evaluate the user's overall influence on the project's development. Provide a concise summary in bullet-point format.".to_string());
 
     // let usr_prompt_1 = &format!("Given the commit logs: {commits_summary} and issue records: {issues_summary}, analyze and identify the top 1-3 significant contributions made by the user to the project. Your task is to recognize the key areas of impact, be it in the codebase, project documentation, or other aspects, even in the presence of insufficient data or lack of direct correlations. Create a list of these significant contributions without directly replicating phrases from the source data. This list will be used in the next step to construct a detailed narrative of the user's journey in the project.");
-    let usr_prompt_1 = &format!("Given the commit logs: {commits_summary} and issue records: {issues_summary}, identify the most significant contributions made by the user. Look for patterns and sequences of events that indicate the user's growth and how they approached problem-solving. Consider major code changes, and initiatives that had substantial impact on the project. Additionally, note any instances where the resolution of an issue led to a specific commit.");
+    let usr_prompt_1 = &format!("Given the commit logs: {}, the issue records: {} and the pull request records: {}, identify the most significant contributions made by the user. Look for patterns and sequences of events that indicate the user's growth and how they approached problem-solving. Consider major code changes, and initiatives that had substantial impact on the project. Additionally, note any instances where the resolution of an issue led to a specific commit or pull request.", or_none(&commits_summary).trim_end(), or_none(&issues_summary).trim_end(), or_none(&pulls_summary).trim_end());
 
     // let usr_prompt_2 = &format!("Using the list of significant contributions identified in the previous step, create a detailed narrative that depicts the user's journey and evolution in the project. Describe the progression of these contributions over time, from their inception to their current status. Highlight the overall impact and significance of these contributions within the project's development. Your narrative should be unique and insightful, capturing the user's influence on the project. Present your findings in a clear, concise, and bullet-point format.");
-    let usr_prompt_2 = &format!("Based on the contributions identified, create a concise bullet-point summary. Highlight the user's key contributions and their influence on the project. Pay attention to their growth over time, and how their responses to issues evolved. Make sure to reference any interconnected events between issues and commits. Avoid replicating phrases from the source data and focus on providing a unique and insightful narrative. Please ensure your answer stayed below 256 tokens.");
+    let usr_prompt_2 = &format!("Based on the contributions identified, create a concise bullet-point summary. Highlight the user's key contributions and their influence on the project. Pay attention to their growth over time, and how their responses to issues evolved. Make sure to reference any interconnected events between issues, pull requests and commits. Avoid replicating phrases from the source data and focus on providing a unique and insightful narrative. Please ensure your answer stayed below {} tokens.", stage.summary_tokens);
 
     chain_of_chat(
+        llm,
+        &stage,
         sys_prompt_1,
         usr_prompt_1,
         "correlate-99",
-        512,
         usr_prompt_2,
-        256,
-        "correlate_commits_issues",
+        "the contribution report",
     )
     .await
 }
 
+/// Shrinks `summaries`, one or more lines per commit or issue, to fit
+/// `max_tokens`. The lines are batched into chunks that fit the `digest`
+/// stage's prompt and each chunk is summarized, repeating on the chunk
+/// summaries until the result fits. `kind` names what is summarized,
+/// `commits`, `issues` or `pull requests`.
+pub async fn condense_summaries(
+    llm: &dyn ChatProvider,
+    kind: &str,
+    summaries: &str,
+    max_tokens: usize,
+) -> Result<String> {
+    let stage = StageConfig::from_env(Stage::Digest);
+    let mut text = summaries.to_string();
+    let mut level = 1;
+    while tokens::count(&text) > max_tokens {
+        let chunks = chunk_lines(&text, stage.data_budget());
+        if chunks.len() <= 1 && level > 1 {
+            // a single chunk summarized again won't get much shorter
+            break;
+        }
+
+        let mut condensed = String::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let sys_prompt_1 = &format!("You are given summaries of a user's {kind} on a GitHub project, newest first. Your task is to condense them while keeping the facts that matter for a report on the user's contributions: dates, the areas of the project touched, the problems solved and their impact.");
+            let usr_prompt_1 = &format!("Given the summaries of {kind}: {chunk}, list the key contributions they describe, in chronological order, each with its date or date range. Group closely related items together.");
+            let usr_prompt_2 = &format!("Based on the list, write a condensed summary of these {kind} as a few lines, newest first, keeping dates and project areas. Please ensure your answer stayed below {} tokens.", stage.summary_tokens);
+
+            let digest = chain_of_chat(
+                llm,
+                &stage,
+                sys_prompt_1,
+                usr_prompt_1,
+                &format!("digest-{kind}-{level}-{i}"),
+                usr_prompt_2,
+                &format!("the {kind} digest, part {} of {}", i + 1, chunks.len()),
+            )
+            .await?;
+            condensed.push_str(&digest);
+            condensed.push('\n');
+        }
+
+        if tokens::count(&condensed) >= tokens::count(&text) {
+            break;
+        }
+        text = condensed;
+        level += 1;
+    }
+    Ok(tokens::truncate(&text, max_tokens).to_string())
+}
+
+/// Packs the lines of `text` into chunks of at most `max_tokens` each,
+/// keeping their order. A line longer than that is cut to fit.
+pub fn chunk_lines(text: &str, max_tokens: usize) -> Vec<String> {
+    let mut chunks = vec![];
+    let mut chunk = String::new();
+    let mut chunk_tokens = 0;
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let line = tokens::truncate(line, max_tokens.saturating_sub(1));
+        let line_tokens = tokens::count(line) + 1;
+        if chunk_tokens + line_tokens > max_tokens && !chunk.is_empty() {
+            chunks.push(std::mem::take(&mut chunk));
+            chunk_tokens = 0;
+        }
+        chunk.push_str(line);
+        chunk.push('\n');
+        chunk_tokens += line_tokens;
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+/// Runs the two step chat of a pipeline stage: the first prompt extracts the
+/// key facts, the second writes the summary from them.
 pub async fn chain_of_chat(
+    llm: &dyn ChatProvider,
+    stage: &StageConfig,
     sys_prompt_1: &str,
     usr_prompt_1: &str,
     chat_id: &str,
-    gen_len_1: u16,
     usr_prompt_2: &str,
-    gen_len_2: u16,
     error_tag: &str,
-) -> Option<String> {
-    let openai = OpenAIFlows::new();
-
-    let co_1 = ChatOptions {
-        model: ChatModel::GPT35Turbo16K,
-        restart: true,
-        system_prompt: Some(sys_prompt_1),
-        max_tokens: Some(gen_len_1),
-        temperature: Some(0.7),
-        ..Default::default()
-    };
-
-    match openai.chat_completion(chat_id, usr_prompt_1, &co_1).await {
-        Ok(res_1) => {
-            let sys_prompt_2 = serde_json::json!([{"role": "system", "content": sys_prompt_1},
-    {"role": "user", "content": usr_prompt_1},
-    {"role": "assistant", "content": &res_1.choice}])
-            .to_string();
-
-            let co_2 = ChatOptions {
-                model: ChatModel::GPT35Turbo16K,
-                restart: false,
-                system_prompt: Some(&sys_prompt_2),
-                max_tokens: Some(gen_len_2),
-                temperature: Some(0.7),
-                ..Default::default()
-            };
-            match openai.chat_completion(chat_id, usr_prompt_2, &co_2).await {
-                Ok(res_2) => {
-                    if res_2.choice.len() < 10 {
-                        log::error!(
-                            "{}, GPT generation went sideway: {:?}",
-                            error_tag,
-                            res_2.choice
-                        );
-                        return None;
-                    }
-                    return Some(res_2.choice);
-                }
-                Err(_e) => log::error!("{}, Step 2 GPT generation error {:?}", error_tag, _e),
-            };
-        }
-        Err(_e) => log::error!("{}, Step 1 GPT generation error {:?}", error_tag, _e),
-    }
+) -> Result<String> {
+    let mut messages = vec![
+        ChatMessage::system(sys_prompt_1),
+        ChatMessage::user(usr_prompt_1),
+    ];
 
-    None
-}
+    let res_1 = llm
+        .chat(&ChatRequest {
+            chat_id,
+            messages: &messages,
+            model: stage.model.as_deref(),
+            max_tokens: stage.extract_tokens,
+            temperature: stage.temperature,
+        })
+        .await
+        .map_err(|_e| {
+            log::error!("{}, Step 1 GPT generation error {:?}", error_tag, _e);
+            Error::Llm {
+                tag: error_tag.to_string(),
+                message: _e,
+            }
+        })?;
 
-pub async fn github_http_fetch(token: &str, url: &str) -> Option<Vec<u8>> {
-    let url = Uri::try_from(url).unwrap();
-    let mut writer = Vec::new();
+    messages.push(ChatMessage::assistant(&res_1));
+    messages.push(ChatMessage::user(usr_prompt_2));
 
-    match Request::new(&url)
-        .method(Method::GET)
-        .header("User-Agent", "flows-network connector")
-        .header("Content-Type", "application/vnd.github.v3+json")
-        .header("Authorization", &format!("Bearer {token}"))
-        .send(&mut writer)
-    {
-        Ok(res) => {
-            if !res.status_code().is_success() {
-                log::error!("Github http error {:?}", res.status_code());
-                return None;
-            };
+    let res_2 = llm
+        .chat(&ChatRequest {
+            chat_id,
+            messages: &messages,
+            model: stage.model.as_deref(),
+            max_tokens: stage.summary_tokens,
+            temperature: stage.temperature,
+        })
+        .await
+        .map_err(|_e| {
+            log::error!("{}, Step 2 GPT generation error {:?}", error_tag, _e);
+            Error::Llm {
+                tag: error_tag.to_string(),
+                message: _e,
+            }
+        })?;
 
-            return Some(writer);
-        }
-        Err(_e) => {
-            log::error!("Error getting response from Github: {:?}", _e);
-        }
+    if res_2.len() < 10 {
+        log::error!("{}, GPT generation went sideway: {:?}", error_tag, res_2);
+        return Err(Error::LlmTruncated {
+            tag: error_tag.to_string(),
+        });
     }
-
-    None
+    Ok(res_2)
 }
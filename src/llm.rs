@@ -0,0 +1,316 @@
+//! Language model backends for the summarization prompts.
+//!
+//! The backend is picked by `llm_provider`:
+//!
+//! * `openai_flows` (default): the flows.network OpenAI integration, which
+//!   offers the models in [`FLOWS_MODELS`]
+//! * `openai`: any OpenAI-compatible chat completions endpoint, e.g. OpenAI
+//!   itself or a self-hosted llama.cpp or vLLM server, at `llm_api_base`
+//!   with the key in `llm_api_key`
+//! * `mock`: canned answers, no model involved, see [`MockProvider`]
+//!
+//! `llm_model` names the model, `gpt-3.5-turbo-16k` by default. A model the
+//! backend doesn't offer is an error on every request, never swapped for
+//! another one.
+
+use async_trait::async_trait;
+use http_req::{request::Method, request::Request, uri::Uri};
+use openai_flows::{
+    chat::{ChatModel, ChatOptions},
+    OpenAIFlows,
+};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::env;
+use std::rc::Rc;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn system(content: &str) -> ChatMessage {
+        ChatMessage::new("system", content)
+    }
+
+    pub fn user(content: &str) -> ChatMessage {
+        ChatMessage::new("user", content)
+    }
+
+    pub fn assistant(content: &str) -> ChatMessage {
+        ChatMessage::new("assistant", content)
+    }
+
+    fn new(role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+}
+
+/// One completion request. `messages` holds the whole conversation so far and
+/// ends with the user message to answer.
+#[derive(Debug, Clone)]
+pub struct ChatRequest<'a> {
+    /// Identifies the conversation, for backends that keep it server side.
+    pub chat_id: &'a str,
+    pub messages: &'a [ChatMessage],
+    /// Overrides the provider's default model.
+    pub model: Option<&'a str>,
+    pub max_tokens: u16,
+    pub temperature: f32,
+}
+
+#[async_trait(?Send)]
+pub trait ChatProvider {
+    /// Returns the assistant's reply, or a description of what went wrong.
+    async fn chat(&self, request: &ChatRequest) -> Result<String, String>;
+}
+
+/// Builds the provider configured in the environment. An unknown
+/// `llm_provider` is logged and falls back to `openai_flows`.
+pub fn from_env() -> Box<dyn ChatProvider> {
+    let provider = env::var("llm_provider").unwrap_or("openai_flows".to_string());
+    let model = env::var("llm_model").unwrap_or("gpt-3.5-turbo-16k".to_string());
+    match provider.trim().to_lowercase().as_str() {
+        "openai" => {
+            let api_base =
+                env::var("llm_api_base").unwrap_or("https://api.openai.com/v1".to_string());
+            let api_key = env::var("llm_api_key").unwrap_or_default();
+            Box::new(OpenAiCompatible::new(&api_base, &api_key, &model))
+        }
+        "mock" => Box::new(MockProvider::new()),
+        "openai_flows" | "" => Box::new(FlowsProvider::new(&model)),
+        other => {
+            log::error!("Unknown llm_provider '{other}', using openai_flows");
+            Box::new(FlowsProvider::new(&model))
+        }
+    }
+}
+
+/// The flows.network OpenAI integration. It keeps the conversation by
+/// `chat_id`, so only the first message starts a new one.
+pub struct FlowsProvider {
+    /// The default model, or why there is none.
+    model: Result<ChatModel, String>,
+}
+
+impl FlowsProvider {
+    /// A provider running `model`. A model the integration doesn't offer
+    /// isn't replaced by another one, whose context window the prompts
+    /// weren't budgeted for; every request fails with an error naming it.
+    pub fn new(model: &str) -> FlowsProvider {
+        let model = flows_model(model);
+        if let Err(e) = &model {
+            log::error!("{e}");
+        }
+        FlowsProvider { model }
+    }
+}
+
+/// The models of the flows.network OpenAI integration.
+pub const FLOWS_MODELS: [&str; 4] = ["gpt-4", "gpt-4-32k", "gpt-3.5-turbo", "gpt-3.5-turbo-16k"];
+
+fn flows_model(name: &str) -> Result<ChatModel, String> {
+    match name.trim() {
+        "gpt-4" => Ok(ChatModel::GPT4),
+        "gpt-4-32k" => Ok(ChatModel::GPT4_32K),
+        "gpt-3.5-turbo" => Ok(ChatModel::GPT35Turbo),
+        "gpt-3.5-turbo-16k" => Ok(ChatModel::GPT35Turbo16K),
+        _ => Err(format!(
+            "openai_flows has no model '{name}', it offers {}",
+            FLOWS_MODELS.join(", ")
+        )),
+    }
+}
+
+#[async_trait(?Send)]
+impl ChatProvider for FlowsProvider {
+    async fn chat(&self, request: &ChatRequest) -> Result<String, String> {
+        let Some((last, history)) = request.messages.split_last() else {
+            return Err("no message to answer".to_string());
+        };
+        let restart = history.iter().all(|m| m.role == "system");
+        let system_prompt = match restart {
+            true => history
+                .iter()
+                .map(|m| m.content.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            false => serde_json::to_string(history).map_err(|e| e.to_string())?,
+        };
+
+        let model = match request.model {
+            Some(name) => flows_model(name)?,
+            None => self.model.clone()?,
+        };
+        let options = ChatOptions {
+            model,
+            restart,
+            system_prompt: Some(&system_prompt),
+            max_tokens: Some(request.max_tokens),
+            temperature: Some(request.temperature),
+            ..Default::default()
+        };
+        OpenAIFlows::new()
+            .chat_completion(request.chat_id, &last.content, &options)
+            .await
+            .map(|res| res.choice)
+    }
+}
+
+/// An OpenAI-compatible `/chat/completions` endpoint.
+pub struct OpenAiCompatible {
+    api_base: String,
+    api_key: String,
+    model: String,
+}
+
+impl OpenAiCompatible {
+    pub fn new(api_base: &str, api_key: &str, model: &str) -> OpenAiCompatible {
+        OpenAiCompatible {
+            api_base: api_base.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CompletionBody<'a> {
+    model: &'a str,
+    messages: &'a [ChatMessage],
+    max_tokens: u16,
+    temperature: f32,
+}
+
+#[derive(Deserialize)]
+struct CompletionResponse {
+    choices: Vec<Choice>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: ChatMessage,
+}
+
+#[async_trait(?Send)]
+impl ChatProvider for OpenAiCompatible {
+    async fn chat(&self, request: &ChatRequest) -> Result<String, String> {
+        let url_str = format!("{}/chat/completions", self.api_base);
+        let url = Uri::try_from(url_str.as_str()).map_err(|e| e.to_string())?;
+        let body = serde_json::to_vec(&CompletionBody {
+            model: request.model.unwrap_or(&self.model),
+            messages: request.messages,
+            max_tokens: request.max_tokens,
+            temperature: request.temperature,
+        })
+        .map_err(|e| e.to_string())?;
+
+        let mut writer = Vec::new();
+        let mut req = Request::new(&url);
+        req.method(Method::POST)
+            .header("Content-Type", "application/json")
+            .header("Content-Length", &body.len())
+            .timeout(Some(Duration::from_secs(300)))
+            .body(&body);
+        if !self.api_key.is_empty() {
+            req.header("Authorization", &format!("Bearer {}", self.api_key));
+        }
+        let res = req.send(&mut writer).map_err(|e| e.to_string())?;
+
+        let status = u16::from(res.status_code());
+        if !(200..300).contains(&status) {
+            return Err(format!(
+                "{url_str} answered {status}: {}",
+                String::from_utf8_lossy(&writer)
+            ));
+        }
+        let completion: CompletionResponse =
+            serde_json::from_slice(&writer).map_err(|e| e.to_string())?;
+        completion
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message.content)
+            .ok_or(format!("{url_str} returned no choices"))
+    }
+}
+
+/// Answers from a script instead of a model, so the pipeline can run offline
+/// and give the same output every time.
+///
+/// Replies are keyed by `chat_id`, or by a prefix of it when the key ends in
+/// `*`, and give one template per step of the conversation; the last one is
+/// repeated if the conversation goes on. A template may use `{chat_id}`,
+/// `{step}` (counting from 1) and `{excerpt}`, the start of the message being
+/// answered. Unscripted chats get [`MockProvider::DEFAULT_TEMPLATE`].
+#[derive(Debug, Clone, Default)]
+pub struct MockProvider {
+    scripts: Vec<(String, Vec<String>)>,
+    requests: Rc<RefCell<Vec<(String, String)>>>,
+}
+
+impl MockProvider {
+    pub const DEFAULT_TEMPLATE: &'static str = "Mock reply for {chat_id}: {excerpt}";
+
+    pub fn new() -> MockProvider {
+        MockProvider::default()
+    }
+
+    /// Scripts the replies of the chats matching `key`. Later scripts take
+    /// precedence over earlier ones.
+    pub fn script(mut self, key: &str, replies: &[&str]) -> MockProvider {
+        let replies = replies.iter().map(|r| r.to_string()).collect();
+        self.scripts.insert(0, (key.to_string(), replies));
+        self
+    }
+
+    /// The `chat_id` and answered message of every request so far. Clones
+    /// share the record.
+    pub fn requests(&self) -> Vec<(String, String)> {
+        self.requests.borrow().clone()
+    }
+
+    fn template(&self, chat_id: &str, step: usize) -> &str {
+        self.scripts
+            .iter()
+            .find(|(key, _)| match key.strip_suffix('*') {
+                Some(prefix) => chat_id.starts_with(prefix),
+                None => chat_id == key,
+            })
+            .and_then(|(_, replies)| replies.get(step).or(replies.last()))
+            .map(|reply| reply.as_str())
+            .unwrap_or(MockProvider::DEFAULT_TEMPLATE)
+    }
+}
+
+#[async_trait(?Send)]
+impl ChatProvider for MockProvider {
+    async fn chat(&self, request: &ChatRequest) -> Result<String, String> {
+        let last = request
+            .messages
+            .last()
+            .map(|m| m.content.as_str())
+            .unwrap_or_default();
+        self.requests
+            .borrow_mut()
+            .push((request.chat_id.to_string(), last.to_string()));
+
+        let step = request
+            .messages
+            .iter()
+            .filter(|m| m.role == "assistant")
+            .count();
+        let excerpt: String = last.chars().take(80).collect();
+        Ok(self
+            .template(request.chat_id, step)
+            .replace("{chat_id}", request.chat_id)
+            .replace("{step}", &(step + 1).to_string())
+            .replace("{excerpt}", &excerpt))
+    }
+}
@@ -0,0 +1,557 @@
+//! Parsing of the `.patch` form of a commit, as served by GitHub: a mail
+//! message in mbox format carrying the commit message, followed by the
+//! unified diff git produces.
+//!
+//! The facts a patch states outright, such as its date, author and which
+//! files it touches and by how much, are taken from it here, so the prompt
+//! carries them as a compact [`Patch::digest`] instead of leaving the model
+//! to find them in the raw text.
+
+use crate::classify::{Classifier, FileKind};
+use crate::tokens;
+use chrono::{DateTime, FixedOffset};
+use std::cmp::Reverse;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Patch {
+    /// The commit SHA from the mbox `From ` line.
+    pub sha: String,
+    pub author: String,
+    pub email: String,
+    pub date: Option<DateTime<FixedOffset>>,
+    /// The first line of the commit message, without the `[PATCH]` tag.
+    pub subject: String,
+    /// The rest of the commit message, sign-offs included.
+    pub body: String,
+    pub files: Vec<FileChange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+}
+
+impl FileStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileStatus::Added => "added",
+            FileStatus::Deleted => "deleted",
+            FileStatus::Modified => "modified",
+            FileStatus::Renamed => "renamed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    /// The path after the commit, or before it for a deleted file.
+    pub path: String,
+    /// The path before the commit, for a renamed file.
+    pub old_path: Option<String>,
+    pub status: FileStatus,
+    pub kind: FileKind,
+    pub additions: usize,
+    pub deletions: usize,
+    pub binary: bool,
+    /// The hunks of the diff, `@@` lines included.
+    pub diff: String,
+}
+
+impl Patch {
+    /// Parses a patch, classifying its files by the built-in rules. Whatever
+    /// is missing or malformed is left empty rather than failing the whole
+    /// patch, so a partial patch still gives its facts.
+    pub fn parse(text: &str) -> Patch {
+        let mut patch = Patch::default();
+        let mut lines = text.lines().peekable();
+
+        // mail headers, up to the first blank line
+        let mut last_header = "";
+        let mut from = String::new();
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+            // `From <sha> <date>` opens the message, `From:` names the author
+            if let Some(rest) = line.strip_prefix("From ") {
+                patch.sha = rest.split_whitespace().next().unwrap_or("").to_string();
+                continue;
+            }
+            // a long header continues on lines starting with whitespace
+            if line.starts_with([' ', '\t']) {
+                let value = match last_header {
+                    "from" => &mut from,
+                    "subject" => &mut patch.subject,
+                    _ => continue,
+                };
+                value.push(' ');
+                value.push_str(line.trim());
+                continue;
+            }
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            last_header = match name.to_lowercase().as_str() {
+                "from" => {
+                    from = value.trim().to_string();
+                    "from"
+                }
+                "date" => {
+                    patch.date = DateTime::parse_from_rfc2822(value.trim()).ok();
+                    "date"
+                }
+                "subject" => {
+                    patch.subject = value.trim().to_string();
+                    "subject"
+                }
+                _ => "other",
+            };
+        }
+        (patch.author, patch.email) = parse_address(&decode_words(&from));
+        patch.subject = strip_patch_tag(&decode_words(&patch.subject)).to_string();
+
+        // the message body, up to the `---` before the diffstat
+        let mut body = Vec::new();
+        while let Some(line) = lines.peek() {
+            if *line == "---" || line.starts_with("diff --git ") {
+                break;
+            }
+            body.push(*line);
+            lines.next();
+        }
+        patch.body = body.join("\n").trim().to_string();
+
+        // the diffstat is skipped, the counts are taken from the diff itself
+        while let Some(line) = lines.next() {
+            if let Some(paths) = line.strip_prefix("diff --git ") {
+                patch.files.push(parse_file(paths, &mut lines));
+            }
+        }
+        patch.classify(&Classifier::default());
+        patch
+    }
+
+    /// Classifies the files again, e.g. with a repository's overrides.
+    pub fn classify(&mut self, classifier: &Classifier) {
+        for file in &mut self.files {
+            file.kind = classifier.classify(&file.path, &file.diff);
+        }
+    }
+
+    pub fn additions(&self) -> usize {
+        self.files.iter().map(|f| f.additions).sum()
+    }
+
+    pub fn deletions(&self) -> usize {
+        self.files.iter().map(|f| f.deletions).sum()
+    }
+
+    /// The lines added and removed per kind of file, e.g.
+    /// `code +12 -3, docs +4 -0`, in the order of [`FileKind::ALL`].
+    pub fn kinds_summary(&self) -> String {
+        FileKind::ALL
+            .iter()
+            .filter_map(|kind| {
+                let files: Vec<&FileChange> =
+                    self.files.iter().filter(|f| f.kind == *kind).collect();
+                if files.is_empty() {
+                    return None;
+                }
+                let additions: usize = files.iter().map(|f| f.additions).sum();
+                let deletions: usize = files.iter().map(|f| f.deletions).sum();
+                Some(format!("{} +{additions} -{deletions}", kind.name()))
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The facts of the patch, one per line, followed by as much of the diff
+    /// as fits, all within `max_tokens`. The message gets at most a quarter
+    /// of the budget and the file list a third, listing the files with the
+    /// most lines changed when not all of them fit. Every listed file gets an
+    /// equal share of what is left for its diff; the share a short diff
+    /// doesn't use passes on to the files after it. Diffs of generated files
+    /// are left out.
+    pub fn digest(&self, max_tokens: usize) -> String {
+        let mut out = String::new();
+        let mut left = max_tokens;
+        if let Some(date) = self.date {
+            push_within(
+                &mut out,
+                &mut left,
+                &format!("Date: {}\n", date.format("%Y/%m/%d")),
+            );
+        }
+        if !self.author.is_empty() {
+            push_within(&mut out, &mut left, &format!("Author: {}\n", self.author));
+        }
+        let subject = shorten(&self.subject, max_tokens / 8);
+        push_within(&mut out, &mut left, &format!("Subject: {subject}\n"));
+        if !self.body.is_empty() {
+            let body = shorten(&self.body, max_tokens / 4);
+            push_within(&mut out, &mut left, &format!("Message:\n{body}\n"));
+        }
+        push_within(
+            &mut out,
+            &mut left,
+            &format!(
+                "Files changed: {}, +{} -{} ({})\n",
+                self.files.len(),
+                self.additions(),
+                self.deletions(),
+                self.kinds_summary()
+            ),
+        );
+
+        let listed = self.top_files((max_tokens / 3).min(left));
+        for (i, file) in self.files.iter().enumerate() {
+            if listed.contains(&i) {
+                push_within(&mut out, &mut left, &format!("- {}\n", file.describe()));
+            }
+        }
+        if listed.len() < self.files.len() {
+            let more = self.files.len() - listed.len();
+            push_within(&mut out, &mut left, &format!("... and {more} more files\n"));
+        }
+
+        let with_diff: Vec<&FileChange> = listed
+            .iter()
+            .map(|i| &self.files[*i])
+            .filter(|f| !f.diff.is_empty() && f.kind != FileKind::Generated)
+            .collect();
+        let marker = "\n[...]";
+        for (i, file) in with_diff.iter().enumerate() {
+            let heading = format!("\nDiff of {}:\n", file.path);
+            let share = left / (with_diff.len() - i);
+            let fixed = tokens::count(&heading) + tokens::count(marker) + tokens::count("\n");
+            if share <= fixed + 16 {
+                continue;
+            }
+            let diff = tokens::truncate(&file.diff, share - fixed);
+            out.push_str(&heading);
+            out.push_str(diff);
+            let mut used = tokens::count(&heading) + tokens::count(diff) + tokens::count("\n");
+            if diff.len() < file.diff.len() {
+                out.push_str(marker);
+                used += tokens::count(marker);
+            }
+            out.push('\n');
+            left -= used;
+        }
+        out
+    }
+
+    /// The indices of the files whose lines fit in `max_tokens`, most lines
+    /// changed first, in the order of the patch. Room is kept for the line
+    /// counting the files left out.
+    fn top_files(&self, max_tokens: usize) -> Vec<usize> {
+        let lines: Vec<usize> = self
+            .files
+            .iter()
+            .map(|file| tokens::count(&format!("- {}\n", file.describe())))
+            .collect();
+        if lines.iter().sum::<usize>() <= max_tokens {
+            return (0..self.files.len()).collect();
+        }
+        let more = tokens::count(&format!("... and {} more files\n", self.files.len()));
+        let mut budget = max_tokens.saturating_sub(more);
+        let mut by_churn: Vec<usize> = (0..self.files.len()).collect();
+        by_churn.sort_by_key(|i| Reverse(self.files[*i].additions + self.files[*i].deletions));
+        let mut top: Vec<usize> = by_churn
+            .into_iter()
+            .take_while(|i| match budget.checked_sub(lines[*i]) {
+                Some(rest) => {
+                    budget = rest;
+                    true
+                }
+                None => false,
+            })
+            .collect();
+        top.sort();
+        top
+    }
+}
+
+/// Appends `text` if it fits in what is `left` of the budget, and charges it.
+fn push_within(out: &mut String, left: &mut usize, text: &str) {
+    let cost = tokens::count(text);
+    if cost <= *left {
+        out.push_str(text);
+        *left -= cost;
+    }
+}
+
+/// `text` cut to `max_tokens`, marked with `[...]` where it was cut.
+fn shorten(text: &str, max_tokens: usize) -> String {
+    if tokens::count(text) <= max_tokens {
+        return text.to_string();
+    }
+    let marker = " [...]";
+    let kept = tokens::truncate(text, max_tokens.saturating_sub(tokens::count(marker)));
+    format!("{}{marker}", kept.trim_end())
+}
+
+impl FileChange {
+    /// A one-line summary, e.g. `src/lib.rs (code, modified, +3 -1)`.
+    pub fn describe(&self) -> String {
+        let path = match &self.old_path {
+            Some(old) => format!("{old} -> {}", self.path),
+            None => self.path.clone(),
+        };
+        match self.binary {
+            true => format!(
+                "{path} ({}, {}, binary)",
+                self.kind.name(),
+                self.status.name()
+            ),
+            false => format!(
+                "{path} ({}, {}, +{} -{})",
+                self.kind.name(),
+                self.status.name(),
+                self.additions,
+                self.deletions
+            ),
+        }
+    }
+}
+
+/// `Jane Doe <jane@example.com>` into name and address.
+fn parse_address(value: &str) -> (String, String) {
+    let value = value.trim();
+    match value.rsplit_once('<') {
+        Some((name, email)) => (
+            name.trim().trim_matches('"').to_string(),
+            email.trim_end_matches('>').trim().to_string(),
+        ),
+        None => (value.to_string(), value.to_string()),
+    }
+}
+
+/// Decodes the RFC 2047 encoded words in a header, e.g.
+/// `=?UTF-8?q?Ren=C3=A9e?=`, which is how git writes names and subjects that
+/// aren't plain ASCII. The whitespace between two encoded words is dropped,
+/// as a header folded in the middle of a word is split into two of them.
+/// Words in other charsets than UTF-8 and Latin-1 are left as they are.
+fn decode_words(value: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = value;
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let (before, word) = rest.split_at(start);
+        match decode_word(word) {
+            Some((text, len)) => {
+                if !(after_word && before.trim().is_empty()) {
+                    decoded.push_str(before);
+                }
+                decoded.push_str(&text);
+                rest = &word[len..];
+                after_word = true;
+            }
+            None => {
+                decoded.push_str(before);
+                decoded.push_str("=?");
+                rest = &word[2..];
+                after_word = false;
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Decodes the encoded word `word` starts with, returning its text and how
+/// long the word is.
+fn decode_word(word: &str) -> Option<(String, usize)> {
+    let (charset, rest) = word.strip_prefix("=?")?.split_once('?')?;
+    let (encoding, rest) = rest.split_once('?')?;
+    let (encoded, _) = rest.split_once("?=")?;
+    if encoded.contains(char::is_whitespace) {
+        return None;
+    }
+    let bytes = match encoding {
+        "Q" | "q" => decode_q(encoded)?,
+        "B" | "b" => decode_base64(encoded)?,
+        _ => return None,
+    };
+    // `UTF-8*en` names the language as well
+    let text = match charset.split('*').next()?.to_lowercase().as_str() {
+        "utf-8" | "us-ascii" => String::from_utf8(bytes).ok()?,
+        "iso-8859-1" | "latin1" => bytes.into_iter().map(char::from).collect(),
+        _ => return None,
+    };
+    let len = charset.len() + encoding.len() + encoded.len() + 6;
+    Some((text, len))
+}
+
+/// Quoted-printable as headers have it, with `_` for a space.
+fn decode_q(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut input = encoded.bytes();
+    while let Some(byte) = input.next() {
+        match byte {
+            b'_' => bytes.push(b' '),
+            b'=' => {
+                let hex = [input.next()?, input.next()?];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    Some(bytes)
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let (mut bits, mut count) = (0u32, 0);
+    for byte in encoded.bytes().take_while(|b| *b != b'=') {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        bits = bits << 6 | u32::from(value);
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// `[PATCH]`, `[PATCH 2/5]` and `[PATCH v2]` in front of a subject.
+fn strip_patch_tag(subject: &str) -> &str {
+    match subject.strip_prefix("[PATCH") {
+        Some(rest) => match rest.split_once(']') {
+            Some((_, subject)) => subject.trim_start(),
+            None => subject,
+        },
+        None => subject,
+    }
+}
+
+/// Reads one file's part of the diff, the `diff --git` line having been read
+/// already, and stops in front of the next file or the mail footer.
+fn parse_file<'a>(
+    paths: &str,
+    lines: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>,
+) -> FileChange {
+    let (old, new) = split_diff_paths(paths);
+    let mut file = FileChange {
+        path: new,
+        old_path: None,
+        status: FileStatus::Modified,
+        kind: FileKind::Other,
+        additions: 0,
+        deletions: 0,
+        binary: false,
+        diff: String::new(),
+    };
+    let mut rename_from = None;
+
+    // extended headers, up to the first hunk
+    while let Some(line) = lines.peek() {
+        if line.starts_with("@@") || line.starts_with("diff --git ") || *line == "-- " {
+            break;
+        }
+        if line.starts_with("new file mode") {
+            file.status = FileStatus::Added;
+        } else if line.starts_with("deleted file mode") {
+            file.status = FileStatus::Deleted;
+            file.path = old.clone();
+        } else if let Some(from) = line.strip_prefix("rename from ") {
+            rename_from = Some(from.to_string());
+        } else if let Some(to) = line.strip_prefix("rename to ") {
+            file.path = to.to_string();
+        } else if line.starts_with("Binary files ") || line.starts_with("GIT binary patch") {
+            file.binary = true;
+        }
+        lines.next();
+    }
+    if let Some(from) = rename_from {
+        file.status = FileStatus::Renamed;
+        file.old_path = Some(from);
+    }
+    if file.binary {
+        // the base85 payload of a binary patch is of no use in a prompt
+        while let Some(line) = lines.peek() {
+            if line.starts_with("diff --git ") || *line == "-- " {
+                break;
+            }
+            lines.next();
+        }
+        return file;
+    }
+
+    // hunks, read by the line counts in their `@@` lines, so a removed line
+    // starting with `-- ` isn't mistaken for the mail footer
+    while let Some(line) = lines.peek() {
+        let Some((mut old_left, mut new_left)) = hunk_lengths(line) else {
+            break;
+        };
+        file.diff.push_str(line);
+        file.diff.push('\n');
+        lines.next();
+        while old_left + new_left > 0 {
+            let Some(line) = lines.next() else { break };
+            match line.chars().next() {
+                Some('+') => {
+                    file.additions += 1;
+                    new_left = new_left.saturating_sub(1);
+                }
+                Some('-') => {
+                    file.deletions += 1;
+                    old_left = old_left.saturating_sub(1);
+                }
+                // `\ No newline at end of file` counts for neither side
+                Some('\\') => {}
+                _ => {
+                    old_left = old_left.saturating_sub(1);
+                    new_left = new_left.saturating_sub(1);
+                }
+            }
+            file.diff.push_str(line);
+            file.diff.push('\n');
+        }
+        if let Some(line) = lines.peek() {
+            if line.starts_with('\\') {
+                file.diff.push_str(line);
+                file.diff.push('\n');
+                lines.next();
+            }
+        }
+    }
+    file
+}
+
+/// The old and new paths of `a/old b/new`.
+fn split_diff_paths(paths: &str) -> (String, String) {
+    let (old, new) = match paths.split_once(" b/") {
+        Some((old, new)) => (old, new),
+        None => (paths, paths),
+    };
+    let old = old.strip_prefix("a/").unwrap_or(old);
+    (old.to_string(), new.to_string())
+}
+
+/// The old and new line counts of `@@ -10,6 +10,9 @@`. A count left out is 1.
+fn hunk_lengths(line: &str) -> Option<(usize, usize)> {
+    let ranges = line.strip_prefix("@@ ")?.split(" @@").next()?;
+    let (old, new) = ranges.split_once(' ')?;
+    let length = |range: &str| -> Option<usize> {
+        match range.split_once(',') {
+            Some((_, len)) => len.parse().ok(),
+            None => range.parse::<usize>().ok().map(|_| 1),
+        }
+    };
+    Some((
+        length(old.strip_prefix('-')?)?,
+        length(new.strip_prefix('+')?)?,
+    ))
+}
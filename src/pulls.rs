@@ -0,0 +1,491 @@
+//! Pull requests: those the user authored, and those of others the user
+//! reviewed. Reviewing is much of a maintainer's work and hardly shows in
+//! commits, so the reviews given are summarized on their own. The pull
+//! requests among the issues involving the user are summarized here too.
+
+use crate::cache::Cache;
+use crate::command::ReportArgs;
+use crate::config::{Stage, StageConfig};
+use crate::error::{Error, Result};
+use crate::github::{Coverage, GithubClient, Paginated};
+use crate::llm::ChatProvider;
+use crate::redact::Redactor;
+use crate::roles;
+use crate::stats::{IssueFacts, ReviewFacts};
+use crate::{
+    chain_of_chat, env_limit, gather_discussion, join_summaries, latest_comments, split_analyses,
+    squeeze_fit_comment_texts, summary_tag, tokens, Analysis,
+};
+use chrono::{DateTime, Utc};
+use github_flows::octocrab::models::issues::Issue;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct Author {
+    login: String,
+}
+
+/// A review, as listed by `/pulls/{number}/reviews`.
+#[derive(Debug, Deserialize)]
+struct Review {
+    user: Option<Author>,
+    body: Option<String>,
+    /// `APPROVED`, `CHANGES_REQUESTED`, `COMMENTED`, `DISMISSED` or `PENDING`.
+    state: String,
+    submitted_at: Option<DateTime<Utc>>,
+}
+
+/// The part of a single pull request that search results leave out.
+#[derive(Debug, Deserialize)]
+struct PullDetail {
+    merged_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    additions: usize,
+    #[serde(default)]
+    deletions: usize,
+    #[serde(default)]
+    changed_files: usize,
+}
+
+/// An inline comment on the diff, as listed by `/pulls/{number}/comments`.
+#[derive(Debug, Deserialize)]
+struct ReviewComment {
+    user: Option<Author>,
+    body: String,
+    path: String,
+    #[serde(default)]
+    diff_hunk: String,
+    created_at: DateTime<Utc>,
+}
+
+impl Review {
+    fn by(&self, user: &str) -> bool {
+        self.user
+            .as_ref()
+            .is_some_and(|author| author.login.eq_ignore_ascii_case(user))
+    }
+}
+
+impl ReviewComment {
+    fn by(&self, user: &str) -> bool {
+        self.user
+            .as_ref()
+            .is_some_and(|author| author.login.eq_ignore_ascii_case(user))
+    }
+}
+
+/// The summaries of the pull requests the user authored and how many of
+/// them they cover.
+#[derive(Debug, Clone, Default)]
+pub struct PullsSummary {
+    pub summaries: String,
+    pub coverage: Coverage,
+}
+
+/// The summaries of the reviews the user gave, what the user did on each
+/// pull request reviewed and how many of them they cover.
+#[derive(Debug, Clone, Default)]
+pub struct ReviewsSummary {
+    pub summaries: String,
+    pub facts: Vec<ReviewFacts>,
+    pub coverage: Coverage,
+}
+
+/// Summarizes the pull requests the user authored. Pull requests that fail
+/// are skipped, unless all of them do, in which case the first error is
+/// returned.
+pub async fn summarize_pulls(
+    github: &GithubClient,
+    llm: &dyn ChatProvider,
+    cache: &dyn Cache,
+    args: &ReportArgs,
+) -> Result<PullsSummary> {
+    let pulls = list_authored_pulls(github, args).await?;
+    let coverage = pulls.coverage("pull requests");
+
+    let mut results = Vec::new();
+    for pull in pulls.items {
+        // those `--role` filters out are left out
+        match analyze_pull(github, llm, cache, args, pull).await {
+            Ok(Some(analysis)) => results.push(analysis.summary),
+            Ok(None) => {}
+            Err(e) => results.push(Err(e)),
+        }
+    }
+
+    Ok(PullsSummary {
+        summaries: join_summaries(results)?,
+        coverage,
+    })
+}
+
+/// Summarizes the reviews the user gave on others' pull requests. Pull
+/// requests that fail are skipped, unless all of them do, in which case the
+/// first error is returned.
+pub async fn summarize_reviews(
+    github: &GithubClient,
+    llm: &dyn ChatProvider,
+    cache: &dyn Cache,
+    args: &ReportArgs,
+) -> Result<ReviewsSummary> {
+    let pulls = list_reviewed_pulls(github, args).await?;
+    let coverage = pulls.coverage("reviewed pull requests");
+
+    let mut results = Vec::new();
+    for pull in pulls.items {
+        results.push(analyze_review(github, llm, cache, args, pull).await);
+    }
+
+    let (summaries, facts) = split_analyses(results);
+    Ok(ReviewsSummary {
+        summaries: join_summaries(summaries)?,
+        facts,
+        coverage,
+    })
+}
+
+/// Lists the pull requests the user opened, newest first, up to
+/// `max_issues` (env, default 90).
+pub async fn list_authored_pulls(
+    github: &GithubClient,
+    args: &ReportArgs,
+) -> Result<Paginated<Issue>> {
+    let ReportArgs {
+        owner, repo, user, ..
+    } = args;
+    search_pulls(
+        github,
+        args,
+        &format!("repo:{owner}/{repo} is:pr author:{user}"),
+    )
+    .await
+}
+
+/// Lists the pull requests of others the user reviewed, newest first, up to
+/// `max_issues` (env, default 90).
+pub async fn list_reviewed_pulls(
+    github: &GithubClient,
+    args: &ReportArgs,
+) -> Result<Paginated<Issue>> {
+    let ReportArgs {
+        owner, repo, user, ..
+    } = args;
+    search_pulls(
+        github,
+        args,
+        &format!("repo:{owner}/{repo} is:pr reviewed-by:{user} -author:{user}"),
+    )
+    .await
+}
+
+async fn search_pulls(
+    github: &GithubClient,
+    args: &ReportArgs,
+    query: &str,
+) -> Result<Paginated<Issue>> {
+    let query = format!("{query}{}", args.range.search_qualifiers());
+    let encoded_query = urlencoding::encode(&query);
+
+    let url_str = github.api_url(&format!(
+        "/search/issues?q={encoded_query}&sort=created&order=desc"
+    ));
+    github.paginate(&url_str, env_limit("max_issues", 90)).await
+}
+
+async fn list_reviews(
+    github: &GithubClient,
+    args: &ReportArgs,
+    number: u64,
+) -> Result<Vec<Review>> {
+    let ReportArgs { owner, repo, .. } = args;
+    let url_str = github.api_url(&format!("/repos/{owner}/{repo}/pulls/{number}/reviews"));
+    let reviews = github.paginate::<Review>(&url_str, usize::MAX).await?;
+    // a pending review is a draft only its author can see
+    Ok(reviews
+        .items
+        .into_iter()
+        .filter(|review| review.state != "PENDING")
+        .collect())
+}
+
+/// Summarizes a pull request involving the user: what it changes, how its
+/// review went and whether it was merged, followed by the user's roles in it.
+/// Returns `None` if the user has none of the roles asked for by `--role`; a
+/// failed summary still comes with the facts. The comments and timeline
+/// aren't fetched again while both the summary and the facts are cached.
+pub async fn analyze_pull(
+    github: &GithubClient,
+    llm: &dyn ChatProvider,
+    cache: &dyn Cache,
+    args: &ReportArgs,
+    pull: Issue,
+) -> Result<Option<Analysis<IssueFacts>>> {
+    let ReportArgs {
+        owner, repo, user, ..
+    } = args;
+    let stage = StageConfig::from_env(Stage::Review);
+    let redactor = Redactor::from_env();
+    let Some(mut discussion) =
+        gather_discussion(github, cache, args, &pull, &stage, &redactor).await
+    else {
+        return Ok(None);
+    };
+    if let Some(summary) = discussion.cached_summary.clone() {
+        return Ok(Some(discussion.analysis(cache, Ok(summary))));
+    }
+
+    let number = pull.number;
+    let title = pull.title.clone();
+    let author = pull.user.login.clone();
+    let body = match &pull.body {
+        Some(body) => squeeze_fit_comment_texts(&redactor.text(body), "```", 600, 0.6),
+        None => "".to_string(),
+    };
+
+    // search results don't say whether it was merged or what it changes
+    let detail_url = github.api_url(&format!("/repos/{owner}/{repo}/pulls/{number}"));
+    let outcome = match github.fetch(&detail_url).await.and_then(|res| {
+        serde_json::from_slice::<PullDetail>(&res).map_err(|e| Error::Json {
+            what: detail_url.clone(),
+            message: e.to_string(),
+        })
+    }) {
+        Ok(detail) => {
+            let state = match (detail.merged_at, pull.closed_at) {
+                (Some(merged_at), _) => format!("was merged on {}", merged_at.format("%Y/%m/%d")),
+                (None, Some(_)) => "was closed without being merged".to_string(),
+                (None, None) => "is still open".to_string(),
+            };
+            format!(
+                "{state}, changing {} file(s) by +{} -{} lines",
+                detail.changed_files, detail.additions, detail.deletions
+            )
+        }
+        Err(e) => {
+            log::error!("Error fetching pull request #{number}: {e}");
+            discussion.cacheable = false;
+            match pull.closed_at {
+                Some(_) => "is closed".to_string(),
+                None => "is still open".to_string(),
+            }
+        }
+    };
+
+    let mut all_text = format!("User '{author}' opened pull request #{number} titled '{title}', which {outcome}, with the following description: '{body}'.");
+    let mut text_tokens = tokens::count(&all_text);
+
+    let reviews = match list_reviews(github, args, number).await {
+        Ok(reviews) => latest_comments(reviews),
+        Err(e) => {
+            log::error!("Error fetching reviews of pull request #{number}: {e}");
+            discussion.cacheable = false;
+            Vec::new()
+        }
+    };
+    let inputs = reviews
+        .into_iter()
+        .map(|review| {
+            let reviewer = review.user.map(|author| author.login).unwrap_or_default();
+            let review_body = squeeze_fit_comment_texts(
+                &redactor.text(review.body.as_deref().unwrap_or_default()),
+                "```",
+                600,
+                0.6,
+            );
+            format!(
+                " {reviewer} reviewed it ({}): {review_body}",
+                review.state.to_lowercase().replace('_', " ")
+            )
+        })
+        .chain(
+            std::mem::take(&mut discussion.comments)
+                .into_iter()
+                .map(|comment| {
+                    let comment_body = match comment.body {
+                        Some(body) => {
+                            squeeze_fit_comment_texts(&redactor.text(&body), "```", 600, 0.6)
+                        }
+                        None => "".to_string(),
+                    };
+                    format!(" {} commented: {comment_body}", comment.user.login)
+                }),
+        );
+    for input in inputs {
+        let remaining = stage.data_budget().saturating_sub(text_tokens);
+        let input_tokens = tokens::count(&input);
+        if input_tokens > remaining {
+            all_text.push_str(tokens::truncate(&input, remaining));
+            break;
+        }
+        all_text.push_str(&input);
+        text_tokens += input_tokens;
+    }
+
+    let sys_prompt_1 = &format!("Given the information that user '{author}' opened a pull request titled '{title}', which {outcome}, your task is to analyze the pull request, the reviews it received and its discussion. Extract key details including the problem the pull request addresses, the changes it makes, the feedback given by reviewers, how the author responded, the outcome of the review and whether it was merged.");
+    let usr_prompt_1 = &format!("Based on the pull request, its reviews and comments: {all_text}, please list the following key details: The problem or need the pull request addresses. The changes it makes. The main points raised by the reviewers, if any. Whether it was approved, needed changes or is still under discussion. Whether it was merged. The role and contribution of the user '{user}' in the pull request{}.", match discussion.facts.roles.is_empty() {
+        true => String::new(),
+        false => format!(", who is involved as {}", roles::names(&discussion.facts.roles)),
+    });
+    let usr_prompt_2 = &format!("Provide a brief summary of what this pull request changes, how its review went and whether it was merged, emphasizing the contribution made by '{user}', ensuring your response stays under {} tokens.", stage.summary_tokens);
+
+    let summary = chain_of_chat(
+        llm,
+        &stage,
+        sys_prompt_1,
+        usr_prompt_1,
+        &format!("pull_{number}"),
+        usr_prompt_2,
+        &format!("pull request summary #{number}"),
+    )
+    .await;
+    Ok(Some(discussion.analysis(cache, summary)))
+}
+
+/// Summarizes the reviews and review comments the user gave on another's
+/// pull request. Fails only if the reviews can't be had; a failed summary
+/// still comes with the facts. The reviews aren't fetched again while both
+/// the summary and the facts are cached.
+pub async fn analyze_review(
+    github: &GithubClient,
+    llm: &dyn ChatProvider,
+    cache: &dyn Cache,
+    args: &ReportArgs,
+    pull: Issue,
+) -> Result<Analysis<ReviewFacts>> {
+    let ReportArgs {
+        owner, repo, user, ..
+    } = args;
+    let stage = StageConfig::from_env(Stage::Review);
+    let redactor = Redactor::from_env();
+    let number = pull.number;
+    let title = pull.title;
+    let author = pull.user.login;
+    let html_url = pull.html_url.to_string();
+
+    let cache_key = format!(
+        "review/{html_url}/{}/{user}/{}",
+        pull.updated_at.timestamp(),
+        summary_tag(&stage, &[redactor.cache_tag()])
+    );
+    let facts_key = format!(
+        "facts/review/{html_url}/{}/{user}",
+        pull.updated_at.timestamp()
+    );
+    let cached_summary = cache.get(&cache_key);
+    let cached_facts = cache
+        .get(&facts_key)
+        .and_then(|facts| serde_json::from_str::<ReviewFacts>(&facts).ok());
+    if let (Some(summary), Some(facts)) = (&cached_summary, cached_facts) {
+        return Ok(Analysis {
+            summary: Ok(format!("{html_url} {}", summary.trim_end())),
+            facts,
+        });
+    }
+
+    // without the reviews there is nothing to summarize
+    let reviews: Vec<Review> = list_reviews(github, args, number)
+        .await?
+        .into_iter()
+        .filter(|review| review.by(user))
+        .collect();
+    let comments_url = github.api_url(&format!("/repos/{owner}/{repo}/pulls/{number}/comments"));
+    let mut cacheable = true;
+    let comments: Vec<ReviewComment> = match github
+        .paginate::<ReviewComment>(&comments_url, usize::MAX)
+        .await
+    {
+        Ok(comments) => comments
+            .items
+            .into_iter()
+            .filter(|comment| comment.by(user))
+            .collect(),
+        Err(e) => {
+            log::error!("Error fetching review comments of pull request #{number}: {e}");
+            cacheable = false;
+            Vec::new()
+        }
+    };
+
+    let facts = ReviewFacts {
+        reviews: reviews
+            .iter()
+            .filter_map(|review| Some((review.state.clone(), review.submitted_at?)))
+            .collect(),
+        comments: comments.iter().map(|comment| comment.created_at).collect(),
+    };
+    if cacheable {
+        if let Ok(json) = serde_json::to_string(&facts) {
+            cache.put(&facts_key, &json);
+        }
+    }
+    if let Some(summary) = cached_summary {
+        return Ok(Analysis {
+            summary: Ok(format!("{html_url} {}", summary.trim_end())),
+            facts,
+        });
+    }
+
+    let description = match pull.body {
+        Some(body) => squeeze_fit_comment_texts(&redactor.text(&body), "```", 300, 0.6),
+        None => "".to_string(),
+    };
+    let mut all_text = format!("User '{author}' opened pull request #{number} titled '{title}', described as: '{description}'.");
+    let mut text_tokens = tokens::count(&all_text);
+    let review_inputs = latest_comments(reviews)
+        .into_iter()
+        .map(|review| {
+            let review_body = squeeze_fit_comment_texts(
+                &redactor.text(review.body.as_deref().unwrap_or_default()),
+                "```",
+                600,
+                0.6,
+            );
+            format!(
+                " {user} reviewed it ({}): {review_body}",
+                review.state.to_lowercase().replace('_', " ")
+            )
+        })
+        .chain(latest_comments(comments).into_iter().map(|comment| {
+            // the last lines of the hunk are the ones commented on
+            let hunk = comment.diff_hunk.lines().rev().take(3).collect::<Vec<_>>();
+            let hunk = hunk.into_iter().rev().collect::<Vec<_>>().join("\n");
+            format!(
+                " {user} commented on {} at '{}': {}",
+                comment.path,
+                redactor.text(&hunk),
+                squeeze_fit_comment_texts(&redactor.text(&comment.body), "```", 300, 0.6)
+            )
+        }));
+    for review_input in review_inputs {
+        let remaining = stage.data_budget().saturating_sub(text_tokens);
+        let input_tokens = tokens::count(&review_input);
+        if input_tokens > remaining {
+            all_text.push_str(tokens::truncate(&review_input, remaining));
+            break;
+        }
+        all_text.push_str(&review_input);
+        text_tokens += input_tokens;
+    }
+
+    let sys_prompt_1 = &format!("Given the information that user '{user}' reviewed the pull request titled '{title}' by '{author}', your task is to analyze the review activity of '{user}'. Extract key details including what the pull request is about, the aspects the reviewer focused on (such as correctness, design, performance, tests or style), the problems the reviewer found, the changes the reviewer asked for, and the reviewer's verdict.");
+    let usr_prompt_1 = &format!("Based on the pull request and the reviews by '{user}': {all_text}, please list the following key details: What the pull request is about. The aspects of the change '{user}' focused on. Problems found and changes requested, if any. The verdict of the reviews.");
+    let usr_prompt_2 = &format!("Provide a brief summary of the code review '{user}' gave on this pull request, emphasizing how it shaped the change, ensuring your response stays under {} tokens.", stage.summary_tokens);
+
+    let summary = chain_of_chat(
+        llm,
+        &stage,
+        sys_prompt_1,
+        usr_prompt_1,
+        &format!("review_{number}"),
+        usr_prompt_2,
+        &format!("review summary #{number}"),
+    )
+    .await
+    .map(|summary| {
+        if cacheable {
+            cache.put(&cache_key, &summary);
+        }
+        format!("{html_url} {}", summary.trim_end())
+    });
+    Ok(Analysis { summary, facts })
+}
@@ -0,0 +1,209 @@
+//! The part the user played on an issue or pull request. `involves:` finds
+//! everything the user opened, was assigned, commented on, closed or was
+//! merely @-mentioned in; the roles tell those apart, so the reports can put
+//! the user's own work first and `--role` can pick out one kind of it.
+//!
+//! Roles come from the issue itself, which records who closed it, its
+//! comments and its timeline, where GitHub records who was mentioned and
+//! assigned.
+
+use crate::error::{Error, Result};
+use crate::github::GithubClient;
+use chrono::{DateTime, Utc};
+use github_flows::octocrab::models::issues::{Comment, Issue};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Author,
+    Assignee,
+    Commenter,
+    /// Mentioned by someone else, without necessarily taking part.
+    Mentioned,
+    Closer,
+}
+
+impl Role {
+    pub const ALL: [Role; 5] = [
+        Role::Author,
+        Role::Assignee,
+        Role::Commenter,
+        Role::Mentioned,
+        Role::Closer,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Role::Author => "author",
+            Role::Assignee => "assignee",
+            Role::Commenter => "commenter",
+            Role::Mentioned => "mentioned",
+            Role::Closer => "closer",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Role> {
+        let name = name.trim().to_lowercase();
+        let name = name.strip_suffix('s').unwrap_or(&name);
+        Role::ALL.into_iter().find(|role| role.name() == name)
+    }
+
+    /// How much the role says about the user's own work on an issue.
+    pub fn weight(&self) -> usize {
+        match self {
+            Role::Author | Role::Closer => 3,
+            Role::Assignee | Role::Commenter => 2,
+            Role::Mentioned => 1,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Login {
+    login: String,
+}
+
+/// The part of a single issue that search results leave out.
+#[derive(Debug, Deserialize)]
+struct IssueDetail {
+    closed_by: Option<Login>,
+}
+
+/// An event, as listed by `/issues/{number}/timeline`. Events of other kinds
+/// have other fields, only these are read.
+#[derive(Debug, Deserialize)]
+pub struct TimelineEvent {
+    #[serde(default)]
+    event: String,
+    actor: Option<Login>,
+    assignee: Option<Login>,
+}
+
+impl TimelineEvent {
+    fn is(&self, event: &str, login: &Option<Login>, user: &str) -> bool {
+        self.event == event
+            && login
+                .as_ref()
+                .is_some_and(|login| login.login.eq_ignore_ascii_case(user))
+    }
+}
+
+/// Lists the whole timeline of an issue or pull request. It is oldest first,
+/// so a cap would drop the latest events of a long-running issue.
+pub async fn list_timeline(
+    github: &GithubClient,
+    owner: &str,
+    repo: &str,
+    number: u64,
+) -> Result<Vec<TimelineEvent>> {
+    let url_str = github.api_url(&format!("/repos/{owner}/{repo}/issues/{number}/timeline"));
+    Ok(github
+        .paginate::<TimelineEvent>(&url_str, usize::MAX)
+        .await?
+        .items)
+}
+
+/// Who closed the issue or pull request `number` last, or `None` while it
+/// is open.
+pub async fn fetch_closer(
+    github: &GithubClient,
+    owner: &str,
+    repo: &str,
+    number: u64,
+) -> Result<Option<String>> {
+    let url_str = github.api_url(&format!("/repos/{owner}/{repo}/issues/{number}"));
+    let res = github.fetch(&url_str).await?;
+    let detail = serde_json::from_slice::<IssueDetail>(&res).map_err(|e| Error::Json {
+        what: url_str,
+        message: e.to_string(),
+    })?;
+    Ok(detail.closed_by.map(|closer| closer.login))
+}
+
+/// When the user closed `issue`, given who closed it.
+pub fn closed_by(user: &str, issue: &Issue, closer: Option<&str>) -> Option<DateTime<Utc>> {
+    issue
+        .closed_at
+        .filter(|_| closer.is_some_and(|closer| closer.eq_ignore_ascii_case(user)))
+}
+
+/// The user's roles on `issue`, in the order of [`Role::ALL`]. Logins are
+/// compared case insensitively, as GitHub does.
+pub fn roles_of(
+    user: &str,
+    issue: &Issue,
+    comments: &[Comment],
+    events: &[TimelineEvent],
+    closer: Option<&str>,
+) -> Vec<Role> {
+    let mut texts = issue
+        .body
+        .iter()
+        .chain(comments.iter().filter_map(|comment| comment.body.as_ref()));
+    let mut roles = Vec::new();
+    if issue.user.login.eq_ignore_ascii_case(user) {
+        roles.push(Role::Author);
+    }
+    if issue
+        .assignees
+        .iter()
+        .any(|assignee| assignee.login.eq_ignore_ascii_case(user))
+        || events
+            .iter()
+            .any(|event| event.is("assigned", &event.assignee, user))
+    {
+        roles.push(Role::Assignee);
+    }
+    if comments
+        .iter()
+        .any(|comment| comment.user.login.eq_ignore_ascii_case(user))
+    {
+        roles.push(Role::Commenter);
+    }
+    // the timeline names the mentioned user as the actor
+    if events
+        .iter()
+        .any(|event| event.is("mentioned", &event.actor, user))
+        || texts.any(|text| mentions(text, user))
+    {
+        roles.push(Role::Mentioned);
+    }
+    if closed_by(user, issue, closer).is_some() {
+        roles.push(Role::Closer);
+    }
+    roles
+}
+
+/// Whether `text` @-mentions `user`. Logins are case insensitive.
+fn mentions(text: &str, user: &str) -> bool {
+    text.match_indices('@').any(|(at, _)| {
+        let rest = &text[at + 1..];
+        rest.get(..user.len())
+            .is_some_and(|login| login.eq_ignore_ascii_case(user))
+            && !rest[user.len()..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '-')
+    })
+}
+
+/// Whether roles pass a `--role` filter; an empty filter passes everything.
+pub fn selected(roles: &[Role], wanted: &[Role]) -> bool {
+    wanted.is_empty() || roles.iter().any(|role| wanted.contains(role))
+}
+
+/// The weight of all the roles, to put the user's own work first.
+pub fn weight(roles: &[Role]) -> usize {
+    roles.iter().map(Role::weight).sum()
+}
+
+/// `author, closer`.
+pub fn names(roles: &[Role]) -> String {
+    roles.iter().map(Role::name).collect::<Vec<_>>().join(", ")
+}
+
+/// ` [author, closer]`, to follow a summary, or nothing without roles.
+pub fn tag(roles: &[Role]) -> String {
+    match roles.is_empty() {
+        true => String::new(),
+        false => format!(" [{}]", names(roles)),
+    }
+}
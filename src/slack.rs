@@ -0,0 +1,58 @@
+//! Where the bot's replies go.
+
+use async_trait::async_trait;
+use slack_flows::send_message_to_channel;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[async_trait(?Send)]
+pub trait SlackSink {
+    async fn send(&self, workspace: &str, channel: &str, text: String);
+}
+
+/// Posts to Slack through the flows.network integration.
+#[derive(Debug, Default)]
+pub struct SlackFlowsSink;
+
+#[async_trait(?Send)]
+impl SlackSink for SlackFlowsSink {
+    async fn send(&self, workspace: &str, channel: &str, text: String) {
+        send_message_to_channel(workspace, channel, text).await
+    }
+}
+
+/// A message recorded by a [`RecordingSink`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentMessage {
+    pub workspace: String,
+    pub channel: String,
+    pub text: String,
+}
+
+/// Keeps the messages instead of posting them. Clones share the record, so a
+/// clone handed to the bot can be inspected through the original.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingSink {
+    messages: Rc<RefCell<Vec<SentMessage>>>,
+}
+
+impl RecordingSink {
+    pub fn new() -> RecordingSink {
+        RecordingSink::default()
+    }
+
+    pub fn messages(&self) -> Vec<SentMessage> {
+        self.messages.borrow().clone()
+    }
+}
+
+#[async_trait(?Send)]
+impl SlackSink for RecordingSink {
+    async fn send(&self, workspace: &str, channel: &str, text: String) {
+        self.messages.borrow_mut().push(SentMessage {
+            workspace: workspace.to_string(),
+            channel: channel.to_string(),
+            text,
+        });
+    }
+}
@@ -0,0 +1,327 @@
+//! Counts computed from GitHub's data, shown above the narrative of a report
+//! so the numbers don't depend on the model.
+//!
+//! The facts of each commit and issue are gathered while they are
+//! summarized, and kept in the cache next to the summary, so a report from
+//! cached summaries has the same numbers as the first one.
+
+use crate::classify::FileKind;
+use crate::date_range::DateRange;
+use crate::github::Coverage;
+use crate::patch::Patch;
+use crate::roles::Role;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What a commit's patch states about it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommitFacts {
+    pub date: Option<DateTime<FixedOffset>>,
+    pub files: Vec<FileFacts>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileFacts {
+    pub path: String,
+    pub kind: FileKind,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+impl From<&Patch> for CommitFacts {
+    fn from(patch: &Patch) -> CommitFacts {
+        CommitFacts {
+            date: patch.date,
+            files: patch
+                .files
+                .iter()
+                .map(|file| FileFacts {
+                    path: file.path.clone(),
+                    kind: file.kind,
+                    additions: file.additions,
+                    deletions: file.deletions,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// What the user did on an issue or pull request.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IssueFacts {
+    pub pull_request: bool,
+    /// When the user opened it.
+    pub opened: Option<DateTime<Utc>>,
+    /// When the user commented on it.
+    pub comments: Vec<DateTime<Utc>>,
+    /// When the user closed it.
+    pub closed: Option<DateTime<Utc>>,
+    pub roles: Vec<Role>,
+}
+
+/// What the user did as a reviewer of another's pull request.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReviewFacts {
+    /// The state, e.g. `APPROVED`, and time of each review the user gave.
+    pub reviews: Vec<(String, DateTime<Utc>)>,
+    /// When the user left comments on the diff.
+    pub comments: Vec<DateTime<Utc>>,
+}
+
+/// A merged pull request, as the issue search lists it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MergedPull {
+    created_at: DateTime<Utc>,
+    pull_request: MergedAt,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MergedAt {
+    merged_at: Option<DateTime<Utc>>,
+}
+
+/// Reviews given, approvals, change requests, review comments and the pull
+/// requests they were on.
+#[derive(Debug, Clone, Copy, Default)]
+struct ReviewCounts {
+    reviews: usize,
+    approved: usize,
+    changes_requested: usize,
+    comments: usize,
+    pulls: usize,
+}
+
+/// The numbers of one report. Each part is only shown if its data was added.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    range: DateRange,
+    commits: Option<usize>,
+    /// All of the user's commits, when more than were counted.
+    commits_total: Option<usize>,
+    lines: BTreeMap<FileKind, (usize, usize)>,
+    files: BTreeSet<String>,
+    issues: Option<(usize, usize, usize)>,
+    /// The issues and pull requests considered and all those involving the
+    /// user, when more than were considered.
+    issues_sample: Option<(usize, usize)>,
+    merged: Option<usize>,
+    /// The merged pull requests listed, when fewer than all of them.
+    merged_listed: Option<usize>,
+    merge_times: Vec<Duration>,
+    reviews: Option<ReviewCounts>,
+    active_days: BTreeSet<NaiveDate>,
+}
+
+impl Stats {
+    /// Stats of activity within `range`.
+    pub fn new(range: DateRange) -> Stats {
+        Stats {
+            range,
+            ..Stats::default()
+        }
+    }
+
+    /// Adds the user's commits, which are listed for the range already.
+    /// When `coverage` shows the list was cut short, the counts are marked as
+    /// those of the latest commits only.
+    pub fn add_commits(&mut self, commits: &[CommitFacts], coverage: &Coverage) {
+        *self.commits.get_or_insert(0) += commits.len();
+        if let Some(total) = coverage.total.filter(|total| *total > coverage.considered) {
+            *self.commits_total.get_or_insert(0) += total;
+        }
+        for commit in commits {
+            if let Some(date) = commit.date {
+                self.active_days
+                    .insert(date.with_timezone(&Utc).date_naive());
+            }
+            for file in &commit.files {
+                let lines = self.lines.entry(file.kind).or_default();
+                lines.0 += file.additions;
+                lines.1 += file.deletions;
+                self.files.insert(file.path.clone());
+            }
+        }
+    }
+
+    /// Adds what the user did on issues. Pull requests count towards the
+    /// active days only. When `coverage` shows the list was cut short, the
+    /// counts are marked as those of the latest issues only.
+    pub fn add_issues(&mut self, issues: &[IssueFacts], coverage: &Coverage) {
+        if let Some(total) = coverage.total.filter(|total| *total > coverage.considered) {
+            let (considered, all) = self.issues_sample.get_or_insert((0, 0));
+            *considered += coverage.considered;
+            *all += total;
+        }
+        let (opened, commented, closed) = self.issues.get_or_insert((0, 0, 0));
+        for issue in issues {
+            let in_range = |time: &DateTime<Utc>| self.range.contains(time.date_naive());
+            let comments: Vec<NaiveDate> = issue
+                .comments
+                .iter()
+                .filter(|time| in_range(time))
+                .map(|time| time.date_naive())
+                .collect();
+            let issue_opened = issue.opened.filter(in_range);
+            let issue_closed = issue.closed.filter(in_range);
+            if !issue.pull_request {
+                *opened += issue_opened.is_some() as usize;
+                *commented += !comments.is_empty() as usize;
+                *closed += issue_closed.is_some() as usize;
+            }
+            self.active_days.extend(comments);
+            self.active_days.extend(
+                issue_opened
+                    .iter()
+                    .chain(&issue_closed)
+                    .map(|t| t.date_naive()),
+            );
+        }
+    }
+
+    /// Adds the user's pull requests merged within the range. They are
+    /// counted by `coverage`'s total, the time to merge is the median of
+    /// those listed.
+    pub fn add_merged_pulls(&mut self, pulls: &[MergedPull], coverage: &Coverage) {
+        let total = coverage.total.unwrap_or(pulls.len()).max(pulls.len());
+        *self.merged.get_or_insert(0) += total;
+        if total > pulls.len() {
+            *self.merged_listed.get_or_insert(0) += pulls.len();
+        }
+        self.merge_times.extend(
+            pulls
+                .iter()
+                .filter_map(|pull| Some(pull.pull_request.merged_at? - pull.created_at)),
+        );
+    }
+
+    /// Adds the reviews the user gave on others' pull requests.
+    pub fn add_reviews(&mut self, pulls: &[ReviewFacts]) {
+        let counts = self.reviews.get_or_insert_with(ReviewCounts::default);
+        for pull in pulls {
+            let in_range = |time: &DateTime<Utc>| self.range.contains(time.date_naive());
+            let reviews: Vec<&(String, DateTime<Utc>)> = pull
+                .reviews
+                .iter()
+                .filter(|(_, time)| in_range(time))
+                .collect();
+            let comments: Vec<&DateTime<Utc>> =
+                pull.comments.iter().filter(|time| in_range(time)).collect();
+            if reviews.is_empty() && comments.is_empty() {
+                continue;
+            }
+            counts.pulls += 1;
+            counts.reviews += reviews.len();
+            counts.approved += reviews.iter().filter(|(s, _)| s == "APPROVED").count();
+            counts.changes_requested += reviews
+                .iter()
+                .filter(|(s, _)| s == "CHANGES_REQUESTED")
+                .count();
+            counts.comments += comments.len();
+            self.active_days.extend(
+                reviews
+                    .iter()
+                    .map(|(_, time)| time)
+                    .chain(comments)
+                    .map(|time| time.date_naive()),
+            );
+        }
+    }
+
+    /// The stats as lines of a Slack message, empty if nothing was added.
+    pub fn render(&self) -> String {
+        let mut lines = Vec::new();
+        if let Some(commits) = self.commits {
+            let (additions, deletions) = self
+                .lines
+                .values()
+                .fold((0, 0), |(a, d), (add, del)| (a + add, d + del));
+            let by_kind = self
+                .lines
+                .iter()
+                .map(|(kind, (add, del))| format!("{} +{add} -{del}", kind.name()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let sample = match self.commits_total {
+                Some(total) => format!(" (the latest of {total})"),
+                None => String::new(),
+            };
+            let mut line = format!(
+                "• Commits: {commits}{sample}, {} touched, +{additions} -{deletions} lines",
+                plural(self.files.len(), "file")
+            );
+            if !by_kind.is_empty() {
+                line.push_str(&format!(" ({by_kind})"));
+            }
+            lines.push(line);
+        }
+        if let Some((opened, commented, closed)) = self.issues {
+            let sample = match self.issues_sample {
+                Some((considered, total)) => {
+                    format!(" (in the latest {considered} of {total} issues and pull requests)")
+                }
+                None => String::new(),
+            };
+            lines.push(format!(
+                "• Issues: {opened} opened, {commented} commented on, {closed} closed{sample}"
+            ));
+        }
+        if let Some(merged) = self.merged {
+            let mut line = format!("• Pull requests merged: {merged}");
+            if let Some(median) = median(&self.merge_times) {
+                line.push_str(&format!(
+                    ", median time to merge {}",
+                    format_duration(median)
+                ));
+                if let Some(listed) = self.merged_listed {
+                    line.push_str(&format!(" (of the latest {listed})"));
+                }
+            }
+            lines.push(line);
+        }
+        if let Some(counts) = self.reviews {
+            lines.push(format!(
+                "• Reviews: {} on {} ({} approved, {} with changes requested), {}",
+                counts.reviews,
+                plural(counts.pulls, "pull request"),
+                counts.approved,
+                counts.changes_requested,
+                plural(counts.comments, "review comment")
+            ));
+        }
+        if lines.is_empty() {
+            return String::new();
+        }
+        lines.push(format!("• Active days: {}", self.active_days.len()));
+        format!("*Stats*\n{}\n", lines.join("\n"))
+    }
+}
+
+fn plural(n: usize, word: &str) -> String {
+    match n {
+        1 => format!("1 {word}"),
+        n => format!("{n} {word}s"),
+    }
+}
+
+fn median(durations: &[Duration]) -> Option<Duration> {
+    let mut sorted = durations.to_vec();
+    sorted.sort();
+    let mid = sorted.len() / 2;
+    match sorted.len() {
+        0 => None,
+        n if n % 2 == 1 => Some(sorted[mid]),
+        _ => Some((sorted[mid - 1] + sorted[mid]) / 2),
+    }
+}
+
+/// `3d 4h`, `5h 12m` or `40m`.
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+    let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+    match (days, hours) {
+        (0, 0) => format!("{minutes}m"),
+        (0, _) => format!("{hours}h {minutes}m"),
+        _ => format!("{days}d {hours}h"),
+    }
+}
@@ -0,0 +1,125 @@
+//! Token estimates for fitting prompts into the model's context window.
+//!
+//! The estimate follows how BPE tokenizers such as OpenAI's `cl100k_base`
+//! split text: runs of letters, digits, punctuation and whitespace are
+//! pre-tokenized separately, and each run costs about one token per few
+//! characters. It errs on the high side, so a prompt that fits by the
+//! estimate fits the real tokenizer too.
+
+/// Estimates the number of tokens `text` encodes to.
+pub fn count(text: &str) -> usize {
+    pieces(text).map(|(_, cost)| cost).sum()
+}
+
+/// The longest prefix of `text` within `max_tokens`, cut at a piece boundary.
+pub fn truncate(text: &str, max_tokens: usize) -> &str {
+    let mut used = 0;
+    for (start, cost) in pieces(text) {
+        if used + cost > max_tokens {
+            return &text[..start];
+        }
+        used += cost;
+    }
+    text
+}
+
+/// The longest suffix of `text` within `max_tokens`, cut at a piece boundary.
+pub fn truncate_start(text: &str, max_tokens: usize) -> &str {
+    let starts: Vec<(usize, usize)> = pieces(text).collect();
+    let mut used = 0;
+    for (i, (_, cost)) in starts.iter().enumerate().rev() {
+        if used + cost > max_tokens {
+            let start = starts.get(i + 1).map_or(text.len(), |(start, _)| *start);
+            return &text[start..];
+        }
+        used += cost;
+    }
+    text
+}
+
+/// Fits `text` into `max_tokens` by cutting out its middle, keeping
+/// `head_share` of the budget for the beginning and the rest for the end.
+pub fn squeeze(text: &str, max_tokens: usize, head_share: f32) -> String {
+    if count(text) <= max_tokens {
+        return text.to_string();
+    }
+    let marker = " [...] ";
+    let budget = max_tokens.saturating_sub(count(marker));
+    let head_tokens = (budget as f32 * head_share.clamp(0.0, 1.0)) as usize;
+    let head = truncate(text, head_tokens);
+    let tail = truncate_start(&text[head.len()..], budget - head_tokens);
+    format!("{}{marker}{}", head.trim_end(), tail.trim_start())
+}
+
+/// The context window of `model` in tokens, for the models the bot knows.
+pub fn context_window(model: &str) -> Option<usize> {
+    let model = model.to_lowercase();
+    let window = match model.as_str() {
+        m if m.starts_with("gpt-4o") || m.starts_with("gpt-4-turbo") => 128_000,
+        m if m.starts_with("gpt-4-32k") => 32_768,
+        m if m.starts_with("gpt-4") => 8_192,
+        m if m.starts_with("gpt-3.5-turbo-16k") => 16_385,
+        m if m.starts_with("gpt-3.5-turbo") => 4_096,
+        _ => return None,
+    };
+    Some(window)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Letter,
+    Digit,
+    Space,
+    Newline,
+    Punct,
+    Other,
+}
+
+fn class(c: char) -> Class {
+    match c {
+        '\n' | '\r' => Class::Newline,
+        c if c.is_whitespace() => Class::Space,
+        c if c.is_ascii_alphabetic() => Class::Letter,
+        c if c.is_ascii_digit() => Class::Digit,
+        c if c.is_ascii() => Class::Punct,
+        _ => Class::Other,
+    }
+}
+
+/// Splits `text` into pre-tokenizer pieces, yielding the byte offset where
+/// each starts and its estimated cost. A single space in front of a word is
+/// part of the word, as in BPE vocabularies.
+fn pieces(text: &str) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let mut chars = text.char_indices().peekable();
+    std::iter::from_fn(move || {
+        let (start, first) = chars.next()?;
+        let mut kind = class(first);
+        let mut len: usize = 1;
+        if first == ' ' {
+            if let Some(&(_, next)) = chars.peek() {
+                if matches!(class(next), Class::Letter | Class::Digit | Class::Punct) {
+                    chars.next();
+                    kind = class(next);
+                }
+            }
+        }
+        while let Some(&(_, c)) = chars.peek() {
+            if class(c) != kind {
+                break;
+            }
+            chars.next();
+            len += 1;
+        }
+        let cost = match kind {
+            // common words are single tokens, long identifiers split into a few
+            Class::Letter => len.div_ceil(6),
+            // digits are grouped by three
+            Class::Digit => len.div_ceil(3),
+            Class::Space | Class::Newline => 1,
+            Class::Punct => len.div_ceil(2),
+            // non-ASCII text, e.g. CJK, is often more than one token per character
+            Class::Other => len * 2,
+        };
+        Some((start, cost))
+    })
+}
@@ -0,0 +1,76 @@
+use github_info_bot::cache::{Cache, FileCache};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A fresh directory under the system's temporary directory, named after the
+/// test and the time so parallel runs don't share it.
+fn temp_dir(name: &str) -> String {
+    // WASI has no notion of a temporary directory, `std::env::temp_dir` panics
+    #[cfg(target_os = "wasi")]
+    let base = PathBuf::from(env::var("TMPDIR").unwrap_or("/tmp".to_string()));
+    #[cfg(not(target_os = "wasi"))]
+    let base = env::temp_dir();
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let dir = base.join(format!("github-info-bot-{name}-{nanos}"));
+    let _ = fs::remove_dir_all(&dir);
+    dir.to_string_lossy().into_owned()
+}
+
+#[test]
+fn file_cache_keeps_entries_across_instances() {
+    let dir = temp_dir("keeps-entries");
+
+    let long_key = format!(
+        "issue/https://github.com/octo-org/octo-repo/issues/7/{}",
+        "x".repeat(300)
+    );
+    let cache = FileCache::new(&dir);
+    assert_eq!(cache.get("commit/a1b2c3d"), None);
+    cache.put("commit/a1b2c3d", "On 2026/09/14, fixed the parser.");
+    cache.put(&long_key, "Alice fixed the crash.");
+    cache.put(&format!("{long_key}y"), "A different issue.");
+
+    let reopened = FileCache::new(&dir);
+    assert_eq!(
+        reopened.get("commit/a1b2c3d").as_deref(),
+        Some("On 2026/09/14, fixed the parser.")
+    );
+    assert_eq!(
+        reopened.get(&long_key).as_deref(),
+        Some("Alice fixed the crash.")
+    );
+    assert_eq!(
+        reopened.get(&format!("{long_key}y")).as_deref(),
+        Some("A different issue.")
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn capped_file_cache_evicts_the_oldest_entries() {
+    let dir = temp_dir("evicts");
+    let cache = FileCache::new(&dir).with_max_bytes(250);
+
+    for page in 1..=3 {
+        cache.put(&format!("http/page{page}"), &"x".repeat(100));
+        // make the write order visible in the modification times
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    assert_eq!(cache.get("http/page1"), None);
+    assert!(cache.get("http/page2").is_some());
+    assert!(cache.get("http/page3").is_some());
+
+    // an entry larger than the cap is still kept, alone
+    cache.put("http/large", &"x".repeat(300));
+    assert!(cache.get("http/large").is_some());
+    assert_eq!(cache.get("http/page2"), None);
+    assert_eq!(cache.get("http/page3"), None);
+
+    let _ = fs::remove_dir_all(&dir);
+}
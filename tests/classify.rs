@@ -0,0 +1,71 @@
+use github_info_bot::classify::{Classifier, FileKind};
+
+#[test]
+fn builtin_rules_tag_common_layouts() {
+    let classifier = Classifier::default();
+    let cases = [
+        ("src/lib.rs", FileKind::Code),
+        ("src/bin/cli.rs", FileKind::Code),
+        ("tests/report_pipeline.rs", FileKind::Test),
+        ("pkg/server/handler_test.go", FileKind::Test),
+        ("web/src/App.test.tsx", FileKind::Test),
+        ("scripts/release.py", FileKind::Code),
+        ("scripts/release", FileKind::Script),
+        ("ci/build.sh", FileKind::Script),
+        ("Makefile", FileKind::Script),
+        ("README.md", FileKind::Docs),
+        ("LICENSE", FileKind::Docs),
+        ("docs/architecture.svg", FileKind::Docs),
+        ("Cargo.toml", FileKind::Config),
+        (".github/workflows/ci.yml", FileKind::Config),
+        ("Dockerfile", FileKind::Config),
+        ("Cargo.lock", FileKind::Generated),
+        ("web/package-lock.json", FileKind::Generated),
+        (
+            "vendor/github.com/pkg/errors/errors.go",
+            FileKind::Generated,
+        ),
+        ("static/app.min.js", FileKind::Generated),
+        ("assets/logo.png", FileKind::Other),
+    ];
+    for (path, kind) in cases {
+        assert_eq!(classifier.classify(path, ""), kind, "{path}");
+    }
+}
+
+#[test]
+fn generated_markers_win_over_the_path() {
+    let classifier = Classifier::default();
+    let generated =
+        "@@ -0,0 +1,3 @@\n+// Code generated by protoc-gen-go. DO NOT EDIT.\n+package api\n+\n";
+    assert_eq!(
+        classifier.classify("api/service.go", generated),
+        FileKind::Generated
+    );
+
+    // further down a file, the words are just a comment
+    let edited = "@@ -40,3 +40,4 @@\n fn run() {\n+    // DO NOT EDIT the order below\n }\n";
+    assert_eq!(classifier.classify("src/run.rs", edited), FileKind::Code);
+}
+
+#[test]
+fn overrides_apply_per_repository() {
+    let entries = "docs/api/=code, proto/=generated, octo-org/octo-repo:schemas/=generated, octo-org/octo-repo:proto/=code, bogus";
+    let this_repo = Classifier::from_overrides(entries, "octo-org", "octo-repo");
+    let other_repo = Classifier::from_overrides(entries, "octo-org", "other-repo");
+
+    assert_eq!(this_repo.classify("docs/api/client.md", ""), FileKind::Code);
+    assert_eq!(
+        this_repo.classify("schemas/user.json", ""),
+        FileKind::Generated
+    );
+    assert_eq!(this_repo.classify("proto/user.proto", ""), FileKind::Code);
+    assert_eq!(
+        other_repo.classify("schemas/user.json", ""),
+        FileKind::Config
+    );
+    assert_eq!(
+        other_repo.classify("proto/user.proto", ""),
+        FileKind::Generated
+    );
+}
@@ -0,0 +1,63 @@
+use github_info_bot::llm::MockProvider;
+use github_info_bot::{chunk_lines, condense_summaries, tokens};
+
+fn commit_summaries(n: usize) -> String {
+    (0..n)
+        .map(|i| format!("On 2026/{:02}/{:02}, reworked module {i} of the parser to report errors with spans, which made diagnostics clearer.\n", i % 12 + 1, i % 28 + 1))
+        .collect()
+}
+
+#[test]
+fn chunks_keep_lines_whole_and_in_order() {
+    let text = commit_summaries(100);
+    let chunks = chunk_lines(&text, 500);
+
+    assert!(chunks.len() > 1);
+    assert!(chunks.iter().all(|chunk| tokens::count(chunk) <= 500));
+    assert_eq!(chunks.concat(), text);
+}
+
+#[tokio::test]
+async fn long_histories_are_condensed_chunk_by_chunk() {
+    let model = MockProvider::new().script(
+        "digest-commits-*",
+        &["key contributions", "Condensed {chat_id}."],
+    );
+    let summaries = commit_summaries(1_500);
+    assert!(tokens::count(&summaries) > 30_000);
+
+    let condensed = condense_summaries(&model, "commits", &summaries, 2_000)
+        .await
+        .unwrap();
+
+    let chats: Vec<String> = model.requests().into_iter().map(|(id, _)| id).collect();
+    let chunks = chats.len() / 2;
+    assert!(chunks >= 2, "{chats:?}");
+    let expected: String = (0..chunks)
+        .map(|i| format!("Condensed digest-commits-1-{i}.\n"))
+        .collect();
+    assert_eq!(condensed, expected);
+
+    // every summary made it into one of the chunks
+    let prompts: String = model
+        .requests()
+        .into_iter()
+        .filter(|(_, prompt)| prompt.starts_with("Given the summaries"))
+        .map(|(_, prompt)| prompt)
+        .collect();
+    assert!(prompts.contains("reworked module 0 of"));
+    assert!(prompts.contains("reworked module 1499 of"));
+}
+
+#[tokio::test]
+async fn summaries_that_fit_are_left_alone() {
+    let model = MockProvider::new();
+    let summaries = commit_summaries(3);
+
+    let condensed = condense_summaries(&model, "commits", &summaries, 2_000)
+        .await
+        .unwrap();
+
+    assert_eq!(condensed, summaries);
+    assert!(model.requests().is_empty());
+}
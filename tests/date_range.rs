@@ -0,0 +1,111 @@
+use chrono::NaiveDate;
+use github_info_bot::date_range::DateRange;
+
+fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap()
+}
+
+/// A Saturday.
+fn today() -> NaiveDate {
+    day(2026, 10, 17)
+}
+
+#[test]
+fn periods_parse() {
+    let cases = [
+        (
+            "2026-03-01",
+            DateRange::between(day(2026, 3, 1), day(2026, 3, 1)),
+        ),
+        (
+            "2026-02",
+            DateRange::between(day(2026, 2, 1), day(2026, 2, 28)),
+        ),
+        (
+            "2024",
+            DateRange::between(day(2024, 1, 1), day(2024, 12, 31)),
+        ),
+        (
+            "Q3 2026",
+            DateRange::between(day(2026, 7, 1), day(2026, 9, 30)),
+        ),
+        (
+            "2025-q4",
+            DateRange::between(day(2025, 10, 1), day(2025, 12, 31)),
+        ),
+        ("7d", DateRange::between(day(2026, 10, 11), today())),
+        ("2w", DateRange::between(day(2026, 10, 4), today())),
+        ("1m", DateRange::between(day(2026, 9, 18), today())),
+        ("1y", DateRange::between(day(2025, 10, 18), today())),
+        (
+            "last 7 days",
+            DateRange::between(day(2026, 10, 11), today()),
+        ),
+        (
+            "past 2 weeks",
+            DateRange::between(day(2026, 10, 4), today()),
+        ),
+        ("last month", DateRange::between(day(2026, 9, 18), today())),
+        ("today", DateRange::between(today(), today())),
+        (
+            "yesterday",
+            DateRange::between(day(2026, 10, 16), day(2026, 10, 16)),
+        ),
+        ("this week", DateRange::between(day(2026, 10, 12), today())),
+        (
+            "this quarter",
+            DateRange::between(day(2026, 10, 1), today()),
+        ),
+        (
+            "2026-01..2026-03",
+            DateRange::between(day(2026, 1, 1), day(2026, 3, 31)),
+        ),
+        (
+            "2026-09-01..",
+            DateRange {
+                since: Some(day(2026, 9, 1)),
+                until: None,
+            },
+        ),
+        (
+            "..2025",
+            DateRange {
+                since: None,
+                until: Some(day(2025, 12, 31)),
+            },
+        ),
+    ];
+    for (expr, expected) in cases {
+        assert_eq!(DateRange::parse(expr, today()), Some(expected), "{expr}");
+    }
+}
+
+#[test]
+fn reversed_ranges_parse_as_empty() {
+    let range = DateRange::parse("2026-03..2026-01", today()).unwrap();
+    assert_eq!(range, DateRange::between(day(2026, 3, 1), day(2026, 1, 31)));
+    assert!(range.is_empty());
+    assert!(!DateRange::parse("2026-01..2026-03", today())
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn nonsense_and_overflowing_periods_are_rejected() {
+    for expr in [
+        "",
+        "someday",
+        "0d",
+        "last 0 weeks",
+        "Q5 2026",
+        "2026-13",
+        "99999999d",
+        "4294967295w",
+        "99999999m",
+        "last 4294967295 quarters",
+        "last 4294967295 years",
+        "99999999d..2026",
+    ] {
+        assert_eq!(DateRange::parse(expr, today()), None, "{expr}");
+    }
+}
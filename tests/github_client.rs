@@ -0,0 +1,174 @@
+use async_trait::async_trait;
+use github_info_bot::cache::MemoryCache;
+use github_info_bot::error::{Error, Result};
+use github_info_bot::github::{
+    EnterpriseHosts, FixtureTransport, GithubClient, HttpResponse, Transport,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const COMMENTS: &str = "https://api.github.com/repos/octo-org/octo-repo/issues/7/comments";
+
+fn response(etag: &str, link: &str, body: &str) -> HttpResponse {
+    HttpResponse {
+        status: 200,
+        headers: vec![
+            ("ETag".to_string(), etag.to_string()),
+            ("Link".to_string(), link.to_string()),
+        ],
+        body: body.as_bytes().to_vec(),
+    }
+}
+
+/// Records the `Authorization` header sent with each URL.
+#[derive(Clone, Default)]
+struct AuthRecorder(Rc<RefCell<Vec<(String, String)>>>);
+
+#[async_trait(?Send)]
+impl Transport for AuthRecorder {
+    async fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<HttpResponse> {
+        let authorization = headers
+            .iter()
+            .find(|(key, _)| *key == "Authorization")
+            .map_or(String::new(), |(_, value)| value.to_string());
+        self.0.borrow_mut().push((url.to_string(), authorization));
+        Ok(HttpResponse {
+            status: 200,
+            headers: vec![],
+            body: b"{}".to_vec(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn each_host_gets_its_own_token() {
+    let hosts = EnterpriseHosts::parse(
+        "ghe.example.com=ghe-token, http://lab.example.com:8080/=lab-token, bare.example.com",
+    );
+    let recorder = AuthRecorder::default();
+    let client = || {
+        GithubClient::new(
+            Box::new(recorder.clone()),
+            "default-token",
+            "https://api.github.com",
+        )
+    };
+
+    let ghe = client()
+        .for_host("https://ghe.example.com", &hosts)
+        .unwrap();
+    ghe.fetch(&ghe.api_url("/repos/o/r")).await.unwrap();
+    let lab = client()
+        .for_host("http://lab.example.com:8080", &hosts)
+        .unwrap();
+    lab.fetch(&lab.api_url("/repos/o/r")).await.unwrap();
+    // the configured host is no enterprise host, it keeps its token
+    let public = client().for_host("https://github.com", &hosts).unwrap();
+    public.fetch(&public.api_url("/repos/o/r")).await.unwrap();
+
+    assert_eq!(
+        *recorder.0.borrow(),
+        [
+            (
+                "https://ghe.example.com/api/v3/repos/o/r".to_string(),
+                "Bearer ghe-token".to_string()
+            ),
+            (
+                "http://lab.example.com:8080/api/v3/repos/o/r".to_string(),
+                "Bearer lab-token".to_string()
+            ),
+            (
+                "https://api.github.com/repos/o/r".to_string(),
+                "Bearer default-token".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn hosts_off_the_list_are_refused() {
+    let hosts = EnterpriseHosts::parse("ghe.example.com=ghe-token,bare.example.com");
+    let client = || {
+        GithubClient::new(
+            Box::new(FixtureTransport::new()),
+            "default-token",
+            "https://api.github.com",
+        )
+    };
+    let refused = |host: &str| {
+        matches!(
+            client().for_host(host, &hosts),
+            Err(Error::HostNotAllowed { .. })
+        )
+    };
+
+    assert!(refused("https://evil.example.com"));
+    // listed for https only
+    assert!(refused("http://ghe.example.com"));
+    // listed without a token
+    assert!(refused("https://bare.example.com"));
+    // without a list, only the configured host is reachable
+    assert!(matches!(
+        client().for_host("https://ghe.example.com", &EnterpriseHosts::default()),
+        Err(Error::HostNotAllowed { .. })
+    ));
+}
+
+#[tokio::test]
+async fn unchanged_resources_are_served_from_the_cache() {
+    let link = format!("<{COMMENTS}?page=2>; rel=\"next\"");
+    let transport = FixtureTransport::new()
+        .with_response(COMMENTS, response("W/\"5f1e\"", &link, r#"[{"id":5001}]"#));
+    let cache = MemoryCache::new();
+    let client = GithubClient::new(
+        Box::new(transport.clone()),
+        "test-token",
+        "https://api.github.com",
+    )
+    .with_cache(Box::new(cache.clone()));
+
+    let first = client.get(COMMENTS).await.unwrap();
+    assert_eq!(cache.len(), 1);
+
+    let second = client.get(COMMENTS).await.unwrap();
+    assert_eq!(second.body, first.body);
+    assert_eq!(second.link.as_deref(), Some(link.as_str()));
+    assert_eq!(transport.requests().len(), 2);
+    assert!(!client.was_rate_limited());
+}
+
+#[tokio::test]
+async fn changed_resources_replace_the_cached_copy() {
+    let cache = MemoryCache::new();
+    let client = |transport: &FixtureTransport| {
+        GithubClient::new(
+            Box::new(transport.clone()),
+            "test-token",
+            "https://api.github.com",
+        )
+        .with_cache(Box::new(cache.clone()))
+    };
+
+    let before = FixtureTransport::new().with_response(COMMENTS, response("\"v1\"", "", "[1]"));
+    client(&before).get(COMMENTS).await.unwrap();
+
+    let after = FixtureTransport::new().with_response(COMMENTS, response("\"v2\"", "", "[1,2]"));
+    let res = client(&after).get(COMMENTS).await.unwrap();
+    assert_eq!(res.body, b"[1,2]");
+
+    // the new version is what gets revalidated next time
+    let res = client(&after).get(COMMENTS).await.unwrap();
+    assert_eq!(res.body, b"[1,2]");
+}
+
+#[tokio::test]
+async fn patches_from_the_web_host_are_not_cached() {
+    let patch = "https://github.com/octo-org/octo-repo/commit/a1b2c3d.patch";
+    let transport = FixtureTransport::new().with_response(patch, response("\"p1\"", "", "diff"));
+    let cache = MemoryCache::new();
+    let client = GithubClient::new(Box::new(transport), "test-token", "https://api.github.com")
+        .with_cache(Box::new(cache.clone()));
+
+    assert_eq!(client.fetch(patch).await.unwrap(), b"diff");
+    assert!(cache.is_empty());
+}
@@ -0,0 +1,34 @@
+use github_info_bot::llm::{ChatMessage, ChatProvider, ChatRequest, FlowsProvider};
+
+fn request<'a>(messages: &'a [ChatMessage], model: Option<&'a str>) -> ChatRequest<'a> {
+    ChatRequest {
+        chat_id: "commit-a1b2c",
+        messages,
+        model,
+        max_tokens: 128,
+        temperature: 0.7,
+    }
+}
+
+#[tokio::test]
+async fn openai_flows_refuses_models_it_does_not_offer() {
+    let messages = [
+        ChatMessage::system("Summarize the commit."),
+        ChatMessage::user("diff"),
+    ];
+    let expected =
+        "openai_flows has no model 'gpt-4o', it offers gpt-4, gpt-4-32k, gpt-3.5-turbo, gpt-3.5-turbo-16k";
+
+    // prompts budgeted for gpt-4o's window must not go to a 16k model instead
+    let provider = FlowsProvider::new("gpt-4o");
+    assert_eq!(
+        provider.chat(&request(&messages, None)).await,
+        Err(expected.to_string())
+    );
+    // nor when a stage asks for it
+    let provider = FlowsProvider::new("gpt-4");
+    assert_eq!(
+        provider.chat(&request(&messages, Some("gpt-4o"))).await,
+        Err(expected.to_string())
+    );
+}
@@ -0,0 +1,174 @@
+use github_info_bot::patch::{FileStatus, Patch};
+use github_info_bot::tokens;
+
+const MULTI_FILE: &str = "From 0f1e2d3c4b5a69788796a5b4c3d2e1f0a9b8c7d6 Mon Sep 17 00:00:00 2001
+From: \"Bob Builder\" <bob@example.com>
+Date: Tue, 15 Sep 2026 23:30:00 -0700
+Subject: [PATCH 2/3] Move the lexer into its own module and drop the old
+ logo
+
+The lexer grew too large for parser.rs.
+
+Signed-off-by: Bob Builder <bob@example.com>
+---
+ assets/logo.png             | Bin 1024 -> 0 bytes
+ src/{parser.rs => lexer.rs} |  4 ++--
+ src/sep.rs                  |  2 +-
+ 3 files changed, 3 insertions(+), 3 deletions(-)
+
+diff --git a/assets/logo.png b/assets/logo.png
+deleted file mode 100644
+index 5c6d7e8..0000000
+Binary files a/assets/logo.png and /dev/null differ
+diff --git a/src/parser.rs b/src/lexer.rs
+similarity index 90%
+rename from src/parser.rs
+rename to src/lexer.rs
+index 3b18e51..a9c2f04 100644
+--- a/src/parser.rs
++++ b/src/lexer.rs
+@@ -1,4 +1,4 @@
+-pub fn parse(input: &str) {
+-    lex(input)
++pub fn lex(input: &str) {
++    split(input)
+ }
+ 
+diff --git a/src/sep.rs b/src/sep.rs
+index 1111111..2222222 100644
+--- a/src/sep.rs
++++ b/src/sep.rs
+@@ -1 +1 @@
+--- old separator
++++ new separator
+\\ No newline at end of file
+-- 
+2.43.0
+";
+
+#[test]
+fn reads_the_mail_headers_and_message() {
+    let patch = Patch::parse(MULTI_FILE);
+
+    assert_eq!(patch.sha, "0f1e2d3c4b5a69788796a5b4c3d2e1f0a9b8c7d6");
+    assert_eq!(patch.author, "Bob Builder");
+    assert_eq!(patch.email, "bob@example.com");
+    assert_eq!(
+        patch.date.unwrap().to_rfc3339(),
+        "2026-09-15T23:30:00-07:00"
+    );
+    assert_eq!(
+        patch.subject,
+        "Move the lexer into its own module and drop the old logo"
+    );
+    assert_eq!(
+        patch.body,
+        "The lexer grew too large for parser.rs.\n\nSigned-off-by: Bob Builder <bob@example.com>"
+    );
+}
+
+#[test]
+fn decodes_non_ascii_names_and_subjects() {
+    let patch = Patch::parse(
+        "From 1a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d Mon Sep 17 00:00:00 2001
+From: =?UTF-8?q?Ren=C3=A9e=20Fran=C3=A7ois?= <renee@example.com>
+Date: Wed, 16 Sep 2026 10:00:00 +0200
+Subject: [PATCH] =?UTF-8?q?Teach=20the=20lexer=20about=20na=C3=AFve=20?=
+ =?UTF-8?q?=C3=BCmlauts?= and =?UTF-8?b?Wm/DqyDFjGt1Ym8=?=
+
+Thanks to =?UTF-8?q?stays=20as=20written?=.
+---
+",
+    );
+
+    assert_eq!(patch.author, "Renée François");
+    assert_eq!(patch.email, "renee@example.com");
+    assert_eq!(
+        patch.subject,
+        "Teach the lexer about naïve ümlauts and Zoë Ōkubo"
+    );
+    assert_eq!(patch.body, "Thanks to =?UTF-8?q?stays=20as=20written?=.");
+
+    let latin1 = Patch::parse("From: =?iso-8859-1?q?J=F6rg?= <joerg@example.com>\n\n");
+    assert_eq!(latin1.author, "Jörg");
+}
+
+#[test]
+fn counts_changes_per_file() {
+    let patch = Patch::parse(MULTI_FILE);
+    let files: Vec<String> = patch.files.iter().map(|f| f.describe()).collect();
+
+    assert_eq!(
+        files,
+        [
+            "assets/logo.png (other, deleted, binary)",
+            "src/parser.rs -> src/lexer.rs (code, renamed, +2 -2)",
+            // the lines look like `---` and `+++` headers, but are inside a hunk
+            "src/sep.rs (code, modified, +1 -1)",
+        ]
+    );
+    assert_eq!(patch.files[0].status, FileStatus::Deleted);
+    assert!(patch.files[0].diff.is_empty());
+    assert!(patch.files[2]
+        .diff
+        .ends_with("\\ No newline at end of file\n"));
+    assert!(!patch.files[2].diff.contains("2.43.0"));
+    assert_eq!((patch.additions(), patch.deletions()), (3, 3));
+}
+
+#[test]
+fn digest_states_the_facts_before_the_diffs() {
+    let patch = Patch::parse(include_str!("fixtures/commit.patch"));
+    let digest = patch.digest(2000);
+
+    assert!(digest.starts_with(
+        "Date: 2026/09/14\nAuthor: Alice\nSubject: Fix parser crash on empty input\nFiles changed: 1, +3 -0 (code +3 -0)\n- src/parser.rs (code, modified, +3 -0)\n\nDiff of src/parser.rs:\n@@ -10,5 +10,8 @@"
+    ));
+    assert!(digest.contains("+        return Err(ParseError::Empty);\n"));
+    assert!(!digest.contains("index 3b18e51"));
+    assert!(!digest.contains("2.43.0"));
+}
+
+#[test]
+fn digest_shortens_diffs_but_keeps_every_file() {
+    let patch = Patch::parse(MULTI_FILE);
+    let digest = patch.digest(150);
+
+    assert!(digest.contains("- src/sep.rs (code, modified, +1 -1)\n"));
+    assert!(digest.contains("[...]") || !digest.contains("Diff of src/sep.rs"));
+    assert!(tokens::count(&digest) <= 150);
+}
+
+#[test]
+fn digest_bounds_long_messages_and_many_files() {
+    let mut text = format!(
+        "From 0f1e2d3 Mon Sep 17 00:00:00 2001\nFrom: Bob <bob@example.com>\nSubject: [PATCH] Regenerate the fixtures\n\n{}\n---\n",
+        "The fixtures drifted from the schema. ".repeat(200)
+    );
+    for i in 0..300 {
+        let added = if i == 250 { 40 } else { 1 };
+        text.push_str(&format!(
+            "diff --git a/tests/fixtures/case_{i}.json b/tests/fixtures/case_{i}.json\n--- a/tests/fixtures/case_{i}.json\n+++ b/tests/fixtures/case_{i}.json\n@@ -0,0 +1,{added} @@\n{}",
+            "+{\"id\": 1}\n".repeat(added)
+        ));
+    }
+    let patch = Patch::parse(&text);
+    assert_eq!(patch.files.len(), 300);
+
+    for max_tokens in [100, 400, 2000] {
+        let digest = patch.digest(max_tokens);
+        assert!(
+            tokens::count(&digest) <= max_tokens,
+            "{max_tokens}: {digest}"
+        );
+    }
+    let digest = patch.digest(2000);
+    assert!(digest.contains("Files changed: 300, +339 -0"));
+    assert!(digest.contains("[...]\nFiles changed"));
+    // the file with the most lines changed is listed
+    assert!(digest.contains("- tests/fixtures/case_250.json (test, modified, +40 -0)\n"));
+    assert!(digest.contains(" more files\n"));
+    // among equals, the files first in the patch
+    assert!(digest.contains("case_0.json"));
+    assert!(!digest.contains("case_299.json"));
+}
@@ -0,0 +1,667 @@
+//! Runs whole commands through `Bot::handle` against canned GitHub responses
+//! and a scripted model, and checks what would have been posted to Slack.
+
+use github_info_bot::cache::MemoryCache;
+use github_info_bot::classify::{Classifier, FileKind};
+use github_info_bot::command::ReportArgs;
+use github_info_bot::config::{DebugOutput, Stage, StageConfig};
+use github_info_bot::date_range::DateRange;
+use github_info_bot::github::{EnterpriseHosts, FixtureTransport, GithubClient, HttpResponse};
+use github_info_bot::llm::MockProvider;
+use github_info_bot::redact::Redactor;
+use github_info_bot::slack::RecordingSink;
+use github_info_bot::{analyze_commit, Bot, CommitSettings};
+use serde_json::Value;
+
+const API: &str = "https://api.github.com/repos/octo-org/octo-repo";
+const SHA_1: &str = "a1b2c3d4e5f60718293a4b5c6d7e8f9012345678";
+const SHA_2: &str = "0f1e2d3c4b5a69788796a5b4c3d2e1f012345678";
+const SEARCH: &str = "https://api.github.com/search/issues?q=repo%3Aocto-org%2Focto-repo%20involves%3Aalice&sort=created&order=desc&per_page=90";
+const SEARCH_MERGED: &str = "https://api.github.com/search/issues?q=repo%3Aocto-org%2Focto-repo%20is%3Apr%20is%3Amerged%20author%3Aalice&sort=created&order=desc&per_page=90";
+const SEARCH_AUTHORED: &str = "https://api.github.com/search/issues?q=repo%3Aocto-org%2Focto-repo%20is%3Apr%20author%3Aalice&sort=created&order=desc&per_page=90";
+const SEARCH_REVIEWED: &str = "https://api.github.com/search/issues?q=repo%3Aocto-org%2Focto-repo%20is%3Apr%20reviewed-by%3Aalice%20-author%3Aalice&sort=created&order=desc&per_page=90";
+
+/// GitHub's responses for the report on `user`, who is alice by whatever
+/// case the command spells her login in.
+fn github_fixtures(api_base: &str, web_base: &str, user: &str) -> FixtureTransport {
+    let repo = format!("{api_base}/repos/octo-org/octo-repo");
+    let search = |url: &str| {
+        url.replace("https://api.github.com", api_base)
+            .replace("alice", user)
+    };
+    FixtureTransport::new()
+        .with(&repo, include_str!("fixtures/repo.json"))
+        .with(
+            &format!("{api_base}/users/{user}"),
+            include_str!("fixtures/user.json"),
+        )
+        .with(
+            &format!("{repo}/commits?author={user}&per_page=100"),
+            include_str!("fixtures/commits.json"),
+        )
+        .with(
+            &format!("{web_base}/octo-org/octo-repo/commit/{SHA_1}.patch"),
+            include_str!("fixtures/commit.patch"),
+        )
+        .with(
+            &format!("{web_base}/octo-org/octo-repo/commit/{SHA_2}.patch"),
+            include_str!("fixtures/commit.patch"),
+        )
+        .with(&search(SEARCH), include_str!("fixtures/search_issues.json"))
+        .with(
+            &format!("{repo}/issues/7"),
+            include_str!("fixtures/issue_7.json"),
+        )
+        .with(
+            &format!("{repo}/issues/7/comments?per_page=100"),
+            include_str!("fixtures/issue_7_comments.json"),
+        )
+        .with(
+            &format!("{repo}/issues/7/timeline?per_page=100"),
+            include_str!("fixtures/issue_7_timeline.json"),
+        )
+        .with(
+            &search(SEARCH_MERGED),
+            include_str!("fixtures/search_pulls_merged.json"),
+        )
+        .with(
+            &search(SEARCH_AUTHORED),
+            include_str!("fixtures/search_pulls_merged.json"),
+        )
+        .with(
+            &format!("{repo}/issues/9"),
+            include_str!("fixtures/issue_9.json"),
+        )
+        .with(
+            &format!("{repo}/issues/9/comments?per_page=100"),
+            include_str!("fixtures/issue_9_comments.json"),
+        )
+        .with(
+            &format!("{repo}/issues/9/timeline?per_page=100"),
+            include_str!("fixtures/issue_9_timeline.json"),
+        )
+        .with(
+            &format!("{repo}/pulls/9"),
+            include_str!("fixtures/pull_9.json"),
+        )
+        .with(
+            &format!("{repo}/pulls/9/reviews?per_page=100"),
+            include_str!("fixtures/pull_9_reviews.json"),
+        )
+        .with(
+            &search(SEARCH_REVIEWED),
+            include_str!("fixtures/search_pulls_reviewed.json"),
+        )
+        .with(
+            &format!("{repo}/pulls/12/reviews?per_page=100"),
+            include_str!("fixtures/pull_12_reviews.json"),
+        )
+        .with(
+            &format!("{repo}/pulls/12/comments?per_page=100"),
+            include_str!("fixtures/pull_12_comments.json"),
+        )
+}
+
+fn scripted_model() -> MockProvider {
+    MockProvider::new()
+        .script("commit-*", &["key elements", "Summary of {chat_id}."])
+        .script("issue_7", &["key details", "Alice fixed the crash in #7."])
+        .script(
+            "pull_9",
+            &["key details", "Alice made empty input an error."],
+        )
+        .script(
+            "review_12",
+            &[
+                "key details",
+                // models often end with blank lines, which the report drops
+                "Alice caught a lost last token in the lexer.\n\n",
+            ],
+        )
+        .script(
+            "correlate-99",
+            &["contributions", "* Alice made the parser robust."],
+        )
+}
+
+struct Harness {
+    transport: FixtureTransport,
+    model: MockProvider,
+    slack: RecordingSink,
+    cache: MemoryCache,
+}
+
+impl Harness {
+    fn new() -> Harness {
+        Harness::for_user("alice")
+    }
+
+    fn for_user(user: &str) -> Harness {
+        Harness {
+            transport: github_fixtures("https://api.github.com", "https://github.com", user),
+            model: scripted_model(),
+            slack: RecordingSink::new(),
+            cache: MemoryCache::new(),
+        }
+    }
+
+    fn bot(&self, debug: DebugOutput) -> Bot {
+        Bot {
+            trigger_word: "bot@get".to_string(),
+            github: GithubClient::new(
+                Box::new(self.transport.clone()),
+                "test-token",
+                "https://api.github.com",
+            ),
+            hosts: EnterpriseHosts::parse("ghe.example.com=ghe-token"),
+            llm: Box::new(self.model.clone()),
+            slack: Box::new(self.slack.clone()),
+            cache: Box::new(self.cache.clone()),
+            debug,
+        }
+    }
+
+    async fn run(&self, text: &str) -> Vec<String> {
+        self.bot(DebugOutput::Off)
+            .handle("team", "general", text)
+            .await;
+        self.replies()
+    }
+
+    fn replies(&self) -> Vec<String> {
+        self.slack.messages().into_iter().map(|m| m.text).collect()
+    }
+}
+
+#[tokio::test]
+async fn contrib_report_correlates_commits_and_issues() {
+    let harness = Harness::new();
+    let replies = harness
+        .run("bot@get contrib octo-org/octo-repo alice")
+        .await;
+
+    assert_eq!(
+        replies,
+        ["*Stats*\n• Commits: 2, 1 file touched, +6 -0 lines (code +6 -0)\n• Issues: 0 opened, 1 commented on, 1 closed\n• Pull requests merged: 1, median time to merge 2d 4h\n• Reviews: 2 on 1 pull request (1 approved, 1 with changes requested), 1 review comment\n• Active days: 6\nConsidered all 2 commits. Considered all 2 issues and pull requests.\n* Alice made the parser robust.\n*Code review*\nhttps://github.com/octo-org/octo-repo/pull/12 Alice caught a lost last token in the lexer."]
+    );
+
+    let chats: Vec<String> = harness
+        .model
+        .requests()
+        .into_iter()
+        .map(|(chat_id, _)| chat_id)
+        .collect();
+    assert_eq!(
+        chats,
+        [
+            "commit-a1b2c",
+            "commit-a1b2c",
+            "commit-0f1e2",
+            "commit-0f1e2",
+            "issue_7",
+            "issue_7",
+            "pull_9",
+            "pull_9",
+            "review_12",
+            "review_12",
+            "correlate-99",
+            "correlate-99",
+        ]
+    );
+    let (_, correlation_prompt) = &harness.model.requests()[10];
+    assert!(correlation_prompt.contains("Summary of commit-a1b2c."));
+    // issues and pull requests are sections of their own
+    let (commits, rest) = correlation_prompt.split_once("the issue records:").unwrap();
+    let (issues, pulls) = rest.split_once("the pull request records:").unwrap();
+    assert!(commits.contains("Summary of commit-0f1e2."));
+    assert!(issues.contains("Alice fixed the crash in #7."));
+    assert!(!issues.contains("pull/9"));
+    assert!(pulls.contains("Alice made empty input an error."));
+    assert!(!pulls.contains("issues/7"));
+}
+
+#[tokio::test]
+async fn commits_report_lists_one_summary_per_commit() {
+    let harness = Harness::new();
+    let replies = harness
+        .run("bot@get commits octo-org/octo-repo alice")
+        .await;
+
+    assert_eq!(
+        replies,
+        ["*Stats*\n• Commits: 2, 1 file touched, +6 -0 lines (code +6 -0)\n• Active days: 1\nConsidered all 2 commits.\nSummary of commit-a1b2c. [code +3 -0]\nSummary of commit-0f1e2. [code +3 -0]\n"]
+    );
+    assert!(!harness
+        .transport
+        .requests()
+        .iter()
+        .any(|url| url.contains("/search/")));
+}
+
+#[tokio::test]
+async fn failed_summaries_still_count_in_the_stats() {
+    let mut harness = Harness::new();
+    // a reply this short is taken for a failed generation
+    harness.model = scripted_model()
+        .script("commit-0f1e2", &["key elements", "Oops"])
+        .script("issue_7", &["key details", "Oops"]);
+    let replies = harness
+        .run("bot@get contrib octo-org/octo-repo alice")
+        .await;
+
+    assert!(replies[0].starts_with("*Stats*\n• Commits: 2, 1 file touched, +6 -0 lines (code +6 -0)\n• Issues: 0 opened, 1 commented on, 1 closed\n"));
+    let (_, correlation_prompt) = harness
+        .model
+        .requests()
+        .into_iter()
+        .find(|(id, _)| id == "correlate-99")
+        .unwrap();
+    assert!(correlation_prompt.contains("Summary of commit-a1b2c."));
+    assert!(!correlation_prompt.contains("commit-0f1e2"));
+    assert!(!correlation_prompt.contains("issues/7"));
+}
+
+#[tokio::test]
+async fn issues_report_feeds_comments_to_the_model() {
+    let harness = Harness::new();
+    let replies = harness.run("bot@get issues octo-org/octo-repo alice").await;
+
+    assert_eq!(
+        replies,
+        ["*Stats*\n• Issues: 0 opened, 1 commented on, 1 closed\n• Pull requests merged: 1, median time to merge 2d 4h\n• Active days: 4\nConsidered all 2 issues and pull requests.\n*Issues*\nhttps://github.com/octo-org/octo-repo/issues/7 Alice fixed the crash in #7. [assignee, commenter, closer]\n*Pull requests*\nhttps://github.com/octo-org/octo-repo/pull/9 Alice made empty input an error. [author, commenter]\n"]
+    );
+    let requests = harness.model.requests();
+    let (_, extraction_prompt) = requests.iter().find(|(id, _)| id == "issue_7").unwrap();
+    assert!(extraction_prompt.contains("alice commented: Fixed in a1b2c3d"));
+    // pull requests get their own prompt, with the outcome and the reviews
+    let (_, pull_prompt) = requests.iter().find(|(id, _)| id == "pull_9").unwrap();
+    assert!(pull_prompt.contains("was merged on 2026/09/12, changing 1 file(s) by +6 -0 lines"));
+    assert!(pull_prompt.contains("bob reviewed it (approved): Thanks, this closes #7."));
+    assert!(pull_prompt.contains("bob commented: Does this also cover"));
+}
+
+#[tokio::test]
+async fn role_flag_keeps_only_issues_with_that_role() {
+    let harness = Harness::new();
+    let replies = harness
+        .run("bot@get issues octo-org/octo-repo alice --role closer")
+        .await;
+
+    // alice closed #7, while bob merged her #9
+    assert_eq!(
+        replies,
+        ["*Stats*\n• Issues: 0 opened, 1 commented on, 1 closed\n• Pull requests merged: 1, median time to merge 2d 4h\n• Active days: 2\nConsidered all 2 issues and pull requests.\n*Issues*\nhttps://github.com/octo-org/octo-repo/issues/7 Alice fixed the crash in #7. [assignee, commenter, closer]\n*Pull requests*\nNone in this period.\n"]
+    );
+    let chats: Vec<String> = harness
+        .model
+        .requests()
+        .into_iter()
+        .map(|(chat_id, _)| chat_id)
+        .collect();
+    assert_eq!(chats, ["issue_7", "issue_7"]);
+}
+
+#[tokio::test]
+async fn reviews_report_separates_authored_and_reviewed_pulls() {
+    let harness = Harness::new();
+    let replies = harness
+        .run("bot@get reviews octo-org/octo-repo alice")
+        .await;
+
+    assert_eq!(
+        replies,
+        ["*Stats*\n• Pull requests merged: 1, median time to merge 2d 4h\n• Reviews: 2 on 1 pull request (1 approved, 1 with changes requested), 1 review comment\n• Active days: 2\nConsidered all 1 pull requests. Considered all 1 reviewed pull requests.\n*Pull requests authored*\nhttps://github.com/octo-org/octo-repo/pull/9 Alice made empty input an error. [author, commenter]\n*Reviews given*\nhttps://github.com/octo-org/octo-repo/pull/12 Alice caught a lost last token in the lexer.\n"]
+    );
+    let requests = harness.model.requests();
+    let (_, pull_prompt) = requests.iter().find(|(id, _)| id == "pull_9").unwrap();
+    assert!(pull_prompt.contains("bob reviewed it (approved): Thanks, this closes #7."));
+    // only the reviewer's own words, not the author's replies
+    let (_, review_prompt) = requests.iter().find(|(id, _)| id == "review_12").unwrap();
+    assert!(review_prompt.contains("alice reviewed it (changes requested): The iterator drops"));
+    assert!(review_prompt.contains("alice commented on src/lexer.rs"));
+    assert!(!review_prompt.contains("Good catch"));
+}
+
+/// Moves what `fixture` lists for `url` to a second page, behind a full page
+/// of `padding`.
+fn behind_a_full_page(
+    transport: FixtureTransport,
+    url: &str,
+    fixture: &str,
+    padding: &str,
+) -> FixtureTransport {
+    let items: Vec<Value> = serde_json::from_str(fixture).unwrap();
+    let filler: Value = serde_json::from_str(padding).unwrap();
+    let page = |items: Vec<Value>, link: String| HttpResponse {
+        status: 200,
+        headers: vec![("link".to_string(), link)],
+        body: serde_json::to_vec(&items).unwrap(),
+    };
+    transport
+        .with_response(
+            url,
+            page(vec![filler; 100], format!("<{url}&page=2>; rel=\"next\"")),
+        )
+        .with_response(&format!("{url}&page=2"), page(items, String::new()))
+}
+
+#[tokio::test]
+async fn comments_and_reviews_on_later_pages_still_count() {
+    let mut transport = Harness::new().transport;
+    for (path, fixture, padding) in [
+        (
+            "issues/7/comments",
+            include_str!("fixtures/issue_7_comments.json"),
+            r#"{"id": 1, "node_id": "IC_1", "url": "https://api.github.com/repos/octo-org/octo-repo/issues/comments/1", "html_url": "https://github.com/octo-org/octo-repo/issues/7#issuecomment-1", "body": "+1", "user": {"login": "bob", "id": 1002, "node_id": "MDQ6VXNlcj1002", "avatar_url": "https://avatars.githubusercontent.com/u/1002?v=4", "gravatar_id": "", "url": "https://api.github.com/users/bob", "html_url": "https://github.com/bob", "followers_url": "https://api.github.com/users/bob/followers", "following_url": "https://api.github.com/users/bob/following{/other_user}", "gists_url": "https://api.github.com/users/bob/gists{/gist_id}", "starred_url": "https://api.github.com/users/bob/starred{/owner}{/repo}", "subscriptions_url": "https://api.github.com/users/bob/subscriptions", "organizations_url": "https://api.github.com/users/bob/orgs", "repos_url": "https://api.github.com/users/bob/repos", "events_url": "https://api.github.com/users/bob/events{/privacy}", "received_events_url": "https://api.github.com/users/bob/received_events", "type": "User", "site_admin": false}, "created_at": "2026-09-13T10:00:00Z", "updated_at": "2026-09-13T10:00:00Z"}"#,
+        ),
+        (
+            "pulls/12/reviews",
+            include_str!("fixtures/pull_12_reviews.json"),
+            r#"{"user": {"login": "bob"}, "body": "Looks fine.", "state": "COMMENTED", "submitted_at": "2026-09-13T10:00:00Z"}"#,
+        ),
+        (
+            "pulls/12/comments",
+            include_str!("fixtures/pull_12_comments.json"),
+            r#"{"user": {"login": "bob"}, "body": "Nit.", "path": "src/lexer.rs", "created_at": "2026-09-13T10:00:00Z"}"#,
+        ),
+    ] {
+        let url = format!("{API}/{path}?per_page=100");
+        transport = behind_a_full_page(transport, &url, fixture, padding);
+    }
+    let harness = Harness {
+        transport,
+        ..Harness::new()
+    };
+
+    let replies = harness
+        .run("bot@get contrib octo-org/octo-repo alice")
+        .await;
+    assert_eq!(
+        replies,
+        Harness::new()
+            .run("bot@get contrib octo-org/octo-repo alice")
+            .await
+    );
+    // the prompt has the latest comments, alice's among them
+    let requests = harness.model.requests();
+    let (_, issue_prompt) = requests.iter().find(|(id, _)| id == "issue_7").unwrap();
+    assert!(issue_prompt.contains("Fixed in a1b2c3d"));
+}
+
+#[tokio::test]
+async fn issue_activity_is_found_whatever_the_case_of_the_login() {
+    let replies = Harness::for_user("Alice")
+        .run("bot@get issues octo-org/octo-repo Alice")
+        .await;
+
+    assert_eq!(
+        replies,
+        Harness::new()
+            .run("bot@get issues octo-org/octo-repo alice")
+            .await
+    );
+}
+
+#[tokio::test]
+async fn reviews_are_found_whatever_the_case_of_the_login() {
+    let harness = Harness::for_user("Alice");
+    let replies = harness
+        .run("bot@get reviews octo-org/octo-repo Alice")
+        .await;
+
+    assert!(replies[0].contains(
+        "• Reviews: 2 on 1 pull request (1 approved, 1 with changes requested), 1 review comment\n"
+    ));
+    let requests = harness.model.requests();
+    let (_, review_prompt) = requests.iter().find(|(id, _)| id == "review_12").unwrap();
+    assert!(review_prompt.contains("Alice reviewed it (changes requested): The iterator drops"));
+    assert!(review_prompt.contains("Alice commented on src/lexer.rs"));
+}
+
+#[tokio::test]
+async fn debug_output_posts_intermediate_summaries_first() {
+    let harness = Harness::new();
+    harness
+        .bot(DebugOutput::Channel)
+        .handle("team", "general", "bot@get octo-org/octo-repo alice")
+        .await;
+
+    let replies = harness.replies();
+    assert_eq!(replies.len(), 5);
+    assert!(replies[0].starts_with("[commit summaries]\nSummary of commit-a1b2c."));
+    assert_eq!(
+        replies[1],
+        "[issue summaries]\nhttps://github.com/octo-org/octo-repo/issues/7 Alice fixed the crash in #7. [assignee, commenter, closer]\n"
+    );
+    assert_eq!(
+        replies[2],
+        "[pull request summaries]\nhttps://github.com/octo-org/octo-repo/pull/9 Alice made empty input an error. [author, commenter]\n"
+    );
+    assert_eq!(
+        replies[3],
+        "[review summaries]\nhttps://github.com/octo-org/octo-repo/pull/12 Alice caught a lost last token in the lexer.\n"
+    );
+    assert!(replies[4].contains("* Alice made the parser robust.\n*Code review*\n"));
+}
+
+#[tokio::test]
+async fn unknown_repository_is_reported() {
+    let harness = Harness::new();
+    let replies = harness.run("bot@get octo-org/missing alice").await;
+
+    assert_eq!(
+        replies,
+        ["Sorry, I couldn't build the report: the repository octo-org/missing doesn't exist, or the bot's token can't see it."]
+    );
+    assert!(harness.model.requests().is_empty());
+}
+
+#[tokio::test]
+async fn unknown_user_is_reported() {
+    let harness = Harness::new();
+    let replies = harness.run("bot@get octo-org/octo-repo mallory").await;
+
+    assert_eq!(
+        replies,
+        ["Sorry, I couldn't build the report: there is no GitHub user 'mallory'."]
+    );
+}
+
+#[tokio::test]
+async fn rate_limited_requests_give_up_with_an_error() {
+    let harness = Harness::new();
+    let transport = harness.transport.clone().with_response(
+        &format!("{API}/commits?author=alice&per_page=100"),
+        HttpResponse {
+            status: 403,
+            headers: vec![("X-RateLimit-Remaining".to_string(), "0".to_string())],
+            body: br#"{"message":"API rate limit exceeded"}"#.to_vec(),
+        },
+    );
+    let mut bot = harness.bot(DebugOutput::Off);
+    bot.github = GithubClient::new(Box::new(transport), "test-token", "https://api.github.com");
+    bot.handle(
+        "team",
+        "general",
+        "bot@get commits octo-org/octo-repo alice",
+    )
+    .await;
+
+    let replies = harness.replies();
+    assert_eq!(replies.len(), 1);
+    assert!(
+        replies[0].starts_with("Sorry, I couldn't build the report: GitHub's rate limit was hit"),
+        "{}",
+        replies[0]
+    );
+}
+
+#[tokio::test]
+async fn malformed_command_gets_usage() {
+    let harness = Harness::new();
+    let replies = harness.run("bot@get contrib octo-org/octo-repo").await;
+
+    assert_eq!(replies.len(), 1);
+    assert!(replies[0].starts_with(
+        "Sorry, I couldn't understand that command: missing the GitHub user name.\nUsage: bot@get"
+    ));
+    assert!(harness.transport.requests().is_empty());
+}
+
+#[tokio::test]
+async fn help_needs_no_github_access() {
+    let harness = Harness::new();
+    let replies = harness.run("bot@get help").await;
+
+    assert_eq!(replies.len(), 1);
+    assert!(replies[0].starts_with("Usage: bot@get <subcommand>"));
+    assert!(harness.transport.requests().is_empty());
+}
+
+#[tokio::test]
+async fn messages_without_the_trigger_word_are_ignored() {
+    let harness = Harness::new();
+    let replies = harness
+        .run("has anyone looked at octo-org/octo-repo?")
+        .await;
+
+    assert!(replies.is_empty());
+    assert!(harness.transport.requests().is_empty());
+}
+
+#[tokio::test]
+async fn host_flag_queries_the_enterprise_server() {
+    let mut harness = Harness::new();
+    harness.transport = github_fixtures(
+        "https://ghe.example.com/api/v3",
+        "https://ghe.example.com",
+        "alice",
+    );
+    let replies = harness
+        .run("bot@get commits octo-org/octo-repo alice --host ghe.example.com")
+        .await;
+
+    assert_eq!(
+        replies,
+        ["*Stats*\n• Commits: 2, 1 file touched, +6 -0 lines (code +6 -0)\n• Active days: 1\nConsidered all 2 commits.\nSummary of commit-a1b2c. [code +3 -0]\nSummary of commit-0f1e2. [code +3 -0]\n"]
+    );
+    let requests = harness.transport.requests();
+    assert!(requests
+        .iter()
+        .all(|url| url.starts_with("https://ghe.example.com/")));
+    assert!(requests.contains(&format!(
+        "https://ghe.example.com/octo-org/octo-repo/commit/{SHA_1}.patch"
+    )));
+}
+
+#[tokio::test]
+async fn hosts_the_bot_has_no_token_for_are_refused() {
+    let harness = Harness::new();
+    for host in ["evil.example.com", "http://ghe.example.com"] {
+        harness
+            .run(&format!(
+                "bot@get commits octo-org/octo-repo alice --host {host}"
+            ))
+            .await;
+    }
+
+    assert_eq!(
+        harness.replies(),
+        [
+            "Sorry, I couldn't build the report: https://evil.example.com is not one of the GitHub hosts the bot is configured for (github_enterprise_hosts).",
+            "Sorry, I couldn't build the report: http://ghe.example.com is not one of the GitHub hosts the bot is configured for (github_enterprise_hosts).",
+        ]
+    );
+    assert!(harness.transport.requests().is_empty());
+    assert!(harness.model.requests().is_empty());
+}
+
+#[tokio::test]
+async fn cached_summaries_skip_github_and_the_model() {
+    let harness = Harness::new();
+    let first = harness
+        .run("bot@get contrib octo-org/octo-repo alice")
+        .await;
+    // a summary and the facts of each commit, issue, pull request and review
+    assert_eq!(harness.cache.len(), 10);
+    let requests_before = harness.model.requests().len();
+    let fetches_before = harness.transport.requests().len();
+
+    harness
+        .bot(DebugOutput::Off)
+        .handle(
+            "team",
+            "general",
+            "bot@get contrib octo-org/octo-repo alice",
+        )
+        .await;
+
+    let replies = harness.replies();
+    assert_eq!(replies[1], first[0]);
+    // only the final correlation is asked again
+    let new_chats: Vec<String> = harness.model.requests()[requests_before..]
+        .iter()
+        .map(|(chat_id, _)| chat_id.clone())
+        .collect();
+    assert_eq!(new_chats, ["correlate-99", "correlate-99"]);
+    let new_fetches = &harness.transport.requests()[fetches_before..];
+    assert!(!new_fetches.iter().any(|url| url.ends_with(".patch")
+        || url.contains("/comments")
+        || url.contains("/reviews")
+        || url.contains("/timeline")
+        || url.ends_with("/issues/7")
+        || url.ends_with("/pulls/9")));
+}
+
+#[tokio::test]
+async fn changed_settings_make_the_summaries_again() {
+    let settings = || CommitSettings {
+        stage: StageConfig::default_for(Stage::Commit),
+        classifier: Classifier::default(),
+        redactor: Redactor::default(),
+    };
+    let mut other_model = settings();
+    other_model.stage.model = Some("gpt-4o".to_string());
+    let mut more_redacted = settings();
+    more_redacted.redactor.deny_paths(["deploy/"]);
+    let mut reclassified = settings();
+    reclassified.classifier = Classifier::default().with_override("src/", FileKind::Generated);
+
+    let harness = Harness::new();
+    let github = GithubClient::new(
+        Box::new(harness.transport.clone()),
+        "test-token",
+        "https://api.github.com",
+    );
+    let args = ReportArgs {
+        owner: "octo-org".to_string(),
+        repo: "octo-repo".to_string(),
+        user: "alice".to_string(),
+        range: DateRange::default(),
+        host: None,
+        roles: Vec::new(),
+    };
+    let chats_for = |settings: CommitSettings| {
+        let (github, harness, args) = (&github, &harness, &args);
+        async move {
+            let before = harness.model.requests().len();
+            analyze_commit(
+                github,
+                &harness.model,
+                &harness.cache,
+                &settings,
+                args,
+                SHA_1.to_string(),
+            )
+            .await
+            .unwrap();
+            harness.model.requests().len() - before
+        }
+    };
+
+    assert_eq!(chats_for(settings()).await, 2);
+    assert_eq!(chats_for(settings()).await, 0);
+    assert_eq!(chats_for(other_model).await, 2, "llm_model changed");
+    assert_eq!(chats_for(more_redacted).await, 2, "redact_paths changed");
+    assert_eq!(chats_for(reclassified).await, 2, "file_kinds changed");
+}
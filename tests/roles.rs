@@ -0,0 +1,112 @@
+use github_flows::octocrab::models::issues::{Comment, Issue};
+use github_info_bot::github::{FixtureTransport, GithubClient, HttpResponse};
+use github_info_bot::roles::{
+    closed_by, fetch_closer, list_timeline, roles_of, Role, TimelineEvent,
+};
+use serde_json::{json, Value};
+
+const ISSUE: &str = "https://api.github.com/repos/octo-org/octo-repo/issues/7";
+
+fn search_item(number: u64) -> Issue {
+    let search: Value = serde_json::from_str(include_str!("fixtures/search_issues.json")).unwrap();
+    let item = search["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|item| item["number"] == number)
+        .unwrap();
+    serde_json::from_value(item.clone()).unwrap()
+}
+
+#[test]
+fn roles_come_from_metadata_comments_and_timeline() {
+    let issue = search_item(7);
+    let comments: Vec<Comment> =
+        serde_json::from_str(include_str!("fixtures/issue_7_comments.json")).unwrap();
+    let events: Vec<TimelineEvent> =
+        serde_json::from_str(include_str!("fixtures/issue_7_timeline.json")).unwrap();
+
+    assert_eq!(
+        roles_of("alice", &issue, &comments, &events, Some("alice")),
+        [Role::Assignee, Role::Commenter, Role::Closer]
+    );
+    assert_eq!(
+        closed_by("alice", &issue, Some("alice")).map(|at| at.to_rfc3339()),
+        Some("2026-09-15T08:00:00+00:00".to_string())
+    );
+    // bob opened #7 but didn't close it
+    assert_eq!(
+        roles_of("bob", &issue, &comments, &events, Some("alice")),
+        [Role::Author]
+    );
+    assert_eq!(closed_by("bob", &issue, Some("alice")), None);
+
+    // logins are case insensitive, however the command spells them
+    assert_eq!(
+        roles_of("Alice", &issue, &comments, &events, Some("alice")),
+        [Role::Assignee, Role::Commenter, Role::Closer]
+    );
+    assert!(closed_by("ALICE", &issue, Some("alice")).is_some());
+    assert_eq!(
+        roles_of("Bob", &issue, &comments, &events, Some("alice")),
+        [Role::Author]
+    );
+}
+
+#[test]
+fn mentions_are_found_in_the_text_without_a_timeline() {
+    let mut issue = search_item(9);
+    issue.body = Some("Fixes #7, thanks @Carol-Dev for the report. cc @carol".to_string());
+
+    assert_eq!(
+        roles_of("carol-dev", &issue, &[], &[], None),
+        [Role::Mentioned]
+    );
+    assert_eq!(roles_of("carol", &issue, &[], &[], None), [Role::Mentioned]);
+    // a longer login isn't a mention of a shorter one
+    assert_eq!(roles_of("carol-d", &issue, &[], &[], None), []);
+    assert_eq!(Role::from_name("Closers"), Some(Role::Closer));
+}
+
+#[tokio::test]
+async fn the_closer_is_found_however_long_the_timeline() {
+    let event = |event: &str, login: &str| json!({ "event": event, "actor": { "login": login } });
+    let first_page: Vec<Value> = (0..100).map(|_| event("labeled", "bob")).collect();
+    let last_page = vec![event("mentioned", "alice"), event("closed", "alice")];
+    let page = |events: Vec<Value>, link: &str| HttpResponse {
+        status: 200,
+        headers: vec![("link".to_string(), link.to_string())],
+        body: serde_json::to_vec(&events).unwrap(),
+    };
+    let transport = FixtureTransport::new()
+        .with(ISSUE, include_str!("fixtures/issue_7.json"))
+        .with_response(
+            &format!("{ISSUE}/timeline?per_page=100"),
+            page(
+                first_page,
+                &format!("<{ISSUE}/timeline?per_page=100&page=2>; rel=\"next\""),
+            ),
+        )
+        .with_response(
+            &format!("{ISSUE}/timeline?per_page=100&page=2"),
+            page(last_page, ""),
+        );
+    let github = GithubClient::new(Box::new(transport), "test-token", "https://api.github.com");
+
+    let events = list_timeline(&github, "octo-org", "octo-repo", 7)
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 102);
+    let closer = fetch_closer(&github, "octo-org", "octo-repo", 7)
+        .await
+        .unwrap();
+    assert_eq!(closer.as_deref(), Some("alice"));
+
+    // alice is still assigned, the mention and the close are past the first page
+    let mut issue = search_item(7);
+    issue.body = None;
+    assert_eq!(
+        roles_of("alice", &issue, &[], &events, closer.as_deref()),
+        [Role::Assignee, Role::Mentioned, Role::Closer]
+    );
+}
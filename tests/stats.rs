@@ -0,0 +1,120 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use github_info_bot::date_range::DateRange;
+use github_info_bot::github::Coverage;
+use github_info_bot::roles::Role;
+use github_info_bot::stats::{CommitFacts, IssueFacts, MergedPull, Stats};
+
+fn at(time: &str) -> DateTime<Utc> {
+    time.parse().unwrap()
+}
+
+#[test]
+fn issue_activity_outside_the_range_is_not_counted() {
+    let september = DateRange::between(
+        NaiveDate::from_ymd_opt(2026, 9, 1).unwrap(),
+        NaiveDate::from_ymd_opt(2026, 9, 30).unwrap(),
+    );
+    let mut stats = Stats::new(september);
+    stats.add_issues(
+        &[
+            // opened before the range, discussed and closed within it
+            IssueFacts {
+                pull_request: false,
+                opened: Some(at("2026-08-20T10:00:00Z")),
+                comments: vec![at("2026-09-02T10:00:00Z"), at("2026-09-02T16:00:00Z")],
+                closed: Some(at("2026-09-03T10:00:00Z")),
+                roles: vec![Role::Author, Role::Commenter, Role::Closer],
+            },
+            IssueFacts {
+                pull_request: false,
+                opened: Some(at("2026-09-10T10:00:00Z")),
+                comments: vec![at("2026-10-01T10:00:00Z")],
+                closed: None,
+                roles: vec![Role::Author, Role::Commenter],
+            },
+            // a review comment on a pull request is activity, not an issue
+            IssueFacts {
+                pull_request: true,
+                opened: None,
+                comments: vec![at("2026-09-11T10:00:00Z")],
+                closed: None,
+                roles: vec![Role::Commenter],
+            },
+        ],
+        &Coverage::default(),
+    );
+
+    assert_eq!(
+        stats.render(),
+        "*Stats*\n• Issues: 1 opened, 1 commented on, 1 closed\n• Active days: 4\n"
+    );
+}
+
+#[test]
+fn commit_counts_of_a_capped_list_are_marked_as_a_sample() {
+    let commit = CommitFacts {
+        date: Some("2026-09-14T10:00:00+02:00".parse().unwrap()),
+        files: Vec::new(),
+    };
+    let mut stats = Stats::new(DateRange::default());
+    stats.add_commits(
+        &[commit.clone(), commit],
+        &Coverage {
+            noun: "commits",
+            considered: 2,
+            total: Some(250),
+            incomplete_results: false,
+        },
+    );
+
+    assert_eq!(
+        stats.render(),
+        "*Stats*\n• Commits: 2 (the latest of 250), 0 files touched, +0 -0 lines\n• Active days: 1\n"
+    );
+}
+
+#[test]
+fn issue_and_merge_counts_of_capped_searches_are_marked() {
+    let mut stats = Stats::new(DateRange::default());
+    stats.add_issues(
+        &[IssueFacts {
+            pull_request: false,
+            opened: Some(at("2026-09-14T10:00:00Z")),
+            ..IssueFacts::default()
+        }],
+        &Coverage {
+            noun: "issues and pull requests",
+            considered: 90,
+            total: Some(140),
+            incomplete_results: false,
+        },
+    );
+    // closed a day after it was merged, so only `merged_at` gives 2 days
+    let pull: MergedPull = serde_json::from_str(
+        r#"{
+            "created_at": "2026-09-10T09:00:00Z",
+            "closed_at": "2026-09-13T09:00:00Z",
+            "pull_request": { "merged_at": "2026-09-12T09:00:00Z" }
+        }"#,
+    )
+    .unwrap();
+    stats.add_merged_pulls(
+        &[pull],
+        &Coverage {
+            noun: "merged pull requests",
+            considered: 1,
+            total: Some(120),
+            incomplete_results: false,
+        },
+    );
+
+    assert_eq!(
+        stats.render(),
+        "*Stats*\n• Issues: 1 opened, 0 commented on, 0 closed (in the latest 90 of 140 issues and pull requests)\n• Pull requests merged: 120, median time to merge 2d 0h (of the latest 1)\n• Active days: 1\n"
+    );
+}
+
+#[test]
+fn nothing_added_renders_nothing() {
+    assert_eq!(Stats::new(DateRange::default()).render(), "");
+}
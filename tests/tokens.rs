@@ -0,0 +1,46 @@
+use github_info_bot::squeeze_fit_comment_texts;
+use github_info_bot::tokens;
+
+const PATCH: &str = "diff --git a/src/parser.rs b/src/parser.rs\n+    if input.is_empty() {\n+        return Err(ParseError::Empty);\n+    }\n";
+
+#[test]
+fn truncation_stays_within_the_budget() {
+    let text = PATCH.repeat(50);
+    for budget in [0, 1, 10, 100, 1_000] {
+        let prefix = tokens::truncate(&text, budget);
+        assert!(tokens::count(prefix) <= budget);
+        assert!(text.starts_with(prefix));
+
+        let suffix = tokens::truncate_start(&text, budget);
+        assert!(tokens::count(suffix) <= budget);
+        assert!(text.ends_with(suffix));
+    }
+    assert_eq!(tokens::truncate(PATCH, 10_000), PATCH);
+}
+
+#[test]
+fn squeeze_keeps_both_ends() {
+    let text = format!("first line\n{}last line", PATCH.repeat(100));
+    let squeezed = tokens::squeeze(&text, 200, 0.6);
+
+    assert!(tokens::count(&squeezed) <= 200);
+    assert!(squeezed.starts_with("first line"));
+    assert!(squeezed.ends_with("last line"));
+    assert!(squeezed.contains("[...]"));
+}
+
+#[test]
+fn comment_texts_drop_code_blocks() {
+    let comment = "Tried this:\n```\nlet x = 1;\n```\nand it works.";
+    assert_eq!(
+        squeeze_fit_comment_texts(comment, "```", 600, 0.6),
+        "Tried this:\nand it works.\n"
+    );
+}
+
+#[test]
+fn known_models_have_a_context_window() {
+    assert_eq!(tokens::context_window("gpt-3.5-turbo-16k"), Some(16_385));
+    assert_eq!(tokens::context_window("gpt-4"), Some(8_192));
+    assert_eq!(tokens::context_window("llama-3-8b-instruct"), None);
+}